@@ -0,0 +1,88 @@
+use anyhow::Result;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::emulator::{DisplayBuffer, HEIGHT, WIDTH};
+
+// writes the display as a PNG, each display pixel becoming a scale x scale block.
+pub fn screenshot(frame: &DisplayBuffer, scale: usize, path: &Path) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, (WIDTH * scale) as u32, (HEIGHT * scale) as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&to_rgb(frame, scale))?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+// a file in the given directory named after the current time.
+pub fn timestamped_path(directory: &Path, extension: &str) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+    directory.join(format!("rusty-chip-8-{}.{}", timestamp, extension))
+}
+
+// expands the 0RGB display into scale x scale blocks of RGB bytes.
+pub fn to_rgb(frame: &DisplayBuffer, scale: usize) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(WIDTH * HEIGHT * scale * scale * 3);
+
+    for row in frame.chunks(WIDTH) {
+        for _ in 0..scale {
+            for pixel in row {
+                let [_, r, g, b] = pixel.to_be_bytes();
+                for _ in 0..scale {
+                    rgb.extend_from_slice(&[r, g, b]);
+                }
+            }
+        }
+    }
+
+    rgb
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_rgb() {
+        let mut frame = [0u32; WIDTH * HEIGHT];
+        frame[1] = 0x123456;
+
+        let rgb = to_rgb(&frame, 2);
+
+        assert_eq!(rgb.len(), WIDTH * HEIGHT * 4 * 3);
+        assert_eq!(rgb[..6], [0, 0, 0, 0, 0, 0]);
+        assert_eq!(rgb[6..12], [0x12, 0x34, 0x56, 0x12, 0x34, 0x56]);
+        // the second row of pixels repeats the first.
+        let row = WIDTH * 2 * 3;
+        assert_eq!(rgb[row + 6..row + 12], [0x12, 0x34, 0x56, 0x12, 0x34, 0x56]);
+    }
+
+    #[test]
+    fn test_screenshot() {
+        let path = std::env::temp_dir().join("rusty-chip-8-test-screenshot.png");
+        let mut frame = [0u32; WIDTH * HEIGHT];
+        frame[0] = 0xFFFFFF;
+
+        screenshot(&frame, 4, &path).unwrap();
+
+        let mut reader = png::Decoder::new(File::open(&path).unwrap())
+            .read_info()
+            .unwrap();
+        let mut image = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut image).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((info.width, info.height), (256, 128));
+        assert_eq!(image[..12], [0xFF; 12]);
+        assert_eq!(image[12..15], [0x00; 3]);
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt::Display;
+use std::sync::Mutex;
+
+// Messages for the user, from any thread. While a frontend draws into the terminal they would
+// garble its screen, so they are held back until it gives the terminal up.
+static CONSOLE: Console = Console::init();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Output {
+    Stdout,
+    Stderr,
+}
+
+// messages held back at most, the ones after are only counted.
+const HELD_LIMIT: usize = 100;
+
+struct Console {
+    // None while messages go out right away.
+    held: Mutex<Option<Held>>,
+}
+
+#[derive(Default)]
+struct Held {
+    // each message with the number of times it came in a row.
+    messages: Vec<(Output, String, usize)>,
+    dropped: usize,
+}
+
+impl Console {
+    const fn init() -> Self {
+        Self {
+            held: Mutex::new(None),
+        }
+    }
+
+    fn write(&self, output: Output, message: String) {
+        match self.held.lock().unwrap().as_mut() {
+            Some(held) => held.push(output, message),
+            None => output.write(&message),
+        }
+    }
+
+    fn hold(&self) {
+        self.held.lock().unwrap().get_or_insert_with(Held::default);
+    }
+
+    // stops holding messages back, returning the ones that were.
+    fn release(&self) -> Vec<(Output, String)> {
+        self.held
+            .lock()
+            .unwrap()
+            .take()
+            .map(Held::into_messages)
+            .unwrap_or_default()
+    }
+}
+
+impl Held {
+    // a program stuck on a bad instruction reports it every step, so repeats are counted instead.
+    fn push(&mut self, output: Output, message: String) {
+        if let Some((last_output, last, repeats)) = self.messages.last_mut() {
+            if *last_output == output && *last == message {
+                *repeats += 1;
+                return;
+            }
+        }
+
+        if self.messages.len() < HELD_LIMIT {
+            self.messages.push((output, message, 1));
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    fn into_messages(self) -> Vec<(Output, String)> {
+        let mut messages: Vec<_> = self
+            .messages
+            .into_iter()
+            .map(|(output, message, repeats)| match repeats {
+                1 => (output, message),
+                _ => (output, format!("{} (repeated {} times)", message, repeats)),
+            })
+            .collect();
+        if self.dropped > 0 {
+            messages.push((
+                Output::Stderr,
+                format!("{} more messages were left out.", self.dropped),
+            ));
+        }
+
+        messages
+    }
+}
+
+impl Output {
+    fn write(self, message: &str) {
+        match self {
+            Output::Stdout => println!("{}", message),
+            Output::Stderr => eprintln!("{}", message),
+        }
+    }
+}
+
+// errors, warnings and news about the run, on stderr.
+pub fn report(message: impl Display) {
+    CONSOLE.write(Output::Stderr, message.to_string());
+}
+
+// output the user asked for, such as the debugger's, on stdout.
+pub fn print(message: impl Display) {
+    CONSOLE.write(Output::Stdout, message.to_string());
+}
+
+pub fn hold() {
+    CONSOLE.hold();
+}
+
+// writes out what was held back, in the order it came.
+pub fn release() {
+    for (output, message) in CONSOLE.release() {
+        output.write(&message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hold() {
+        let console = Console::init();
+        assert_eq!(console.release(), vec![]);
+
+        console.hold();
+        console.write(Output::Stderr, "Program halted at 0x228.".to_string());
+        console.write(Output::Stdout, "Instruction: 1228".to_string());
+
+        assert_eq!(
+            console.release(),
+            vec![
+                (Output::Stderr, "Program halted at 0x228.".to_string()),
+                (Output::Stdout, "Instruction: 1228".to_string()),
+            ]
+        );
+        assert_eq!(console.release(), vec![]);
+    }
+
+    #[test]
+    fn test_hold_repeats() {
+        let console = Console::init();
+
+        console.hold();
+        for _ in 0..500 {
+            console.write(Output::Stderr, "Unmatched instruction: 0000".to_string());
+        }
+        for step in 0..HELD_LIMIT + 10 {
+            console.write(Output::Stdout, format!("Instruction: {:04X}", step));
+        }
+
+        let released = console.release();
+        assert_eq!(released.len(), HELD_LIMIT + 1);
+        assert_eq!(
+            released[0],
+            (
+                Output::Stderr,
+                "Unmatched instruction: 0000 (repeated 500 times)".to_string()
+            )
+        );
+        assert_eq!(
+            released[HELD_LIMIT],
+            (
+                Output::Stderr,
+                "11 more messages were left out.".to_string()
+            )
+        );
+    }
+}
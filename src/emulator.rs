@@ -1,128 +1,975 @@
-use chip8::Chip8;
-use std::fs::File;
+use anyhow::{bail, Result};
+use audio_recorder::AudioRecorder;
+use chip8::colour_board::ColourBoard;
+use chip8::stack::Stack;
+use chip8::{Chip8, Fault};
+use clap::ValueEnum;
+use database::RomInfo;
+use frame_buffer::FrameBuffer;
+use halt_detector::HaltDetector;
+use keypad::Keypad;
+use machine::{Machine, Step};
+use menu::Menu;
+use palette::Palette;
+use quirks::{MemoryAccess, Platform, Quirks};
+use rom_source::RomSource;
+use rom_watcher::RomWatcher;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
-use std::time::{self, Duration};
+use std::time::{Duration, Instant};
+use video_recorder::VideoRecorder;
+use vip::{Vip, VipImages};
 
-use crate::window;
-use audio_handler::AudioHandler;
+use crate::capture;
+use crate::console;
+use crate::frontend::{Bindings, Command, Control};
 
-mod audio_handler;
+pub mod audio_recorder;
+mod cartridge;
 mod chip8;
+pub mod database;
+pub mod frame_buffer;
+mod halt_detector;
+pub mod keypad;
+mod machine;
+mod menu;
+pub mod palette;
+pub mod quirks;
+pub mod rom_source;
+mod rom_watcher;
+pub mod validation;
+pub mod video_recorder;
+pub mod vip;
+mod vip_timing;
+
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+pub type DisplayBuffer = [u32; WIDTH * HEIGHT];
+
+// A whole frame as the frontend gets it. The colours alone cannot tell lit pixels apart once
+// palettes and the colour board are in play, so they come along.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub pixels: DisplayBuffer,
+    pub lit: [bool; WIDTH * HEIGHT],
+}
+
+impl Frame {
+    pub fn blank() -> Self {
+        Self {
+            pixels: [0; WIDTH * HEIGHT],
+            lit: [false; WIDTH * HEIGHT],
+        }
+    }
+}
 
 // TODO: move it to a config file
+// instructions per second, unless the ROM database or the command line say otherwise.
 const LOOP_RATE: u64 = 700;
-const SLEEP_DURATION: Duration = time::Duration::from_nanos(1_000_000_000 / LOOP_RATE);
+// the fastest speed there is, it keeps the frame timing arithmetic from overflowing.
+pub const MAX_SPEED: u64 = 1_000_000;
+// timers count down and audio is recorded at this rate of emulated time.
+const FRAME_RATE: u64 = 60;
+// how often the ROM file is checked in watch mode, in real time so crashed games reload too.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+// how often the debugger checks for the key running the next step.
+const DEBUG_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub struct Options {
+    pub debug: bool,
+    // opened before the run starts, so a bad path is reported before anything else happens.
+    pub record_audio: Option<AudioRecorder>,
+    pub record_video: Option<VideoRecorder>,
+    // where hotkey screenshots and recordings are saved, and their pixel size.
+    pub capture_dir: PathBuf,
+    pub capture_scale: usize,
+    // reload the ROM whenever the file changes.
+    pub watch: bool,
+    // directory listed by the menu.
+    pub rom_dir: PathBuf,
+    pub overrides: Overrides,
+    // end the run once the program halts, instead of only reporting it.
+    pub exit_on_halt: bool,
+    // emulated seconds after which the run ends.
+    pub timeout: Option<u64>,
+    // run programs on an emulated COSMAC VIP with these images instead.
+    pub vip: Option<VipImages>,
+}
+
+// How a run ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    // the frontend was closed.
+    Quit,
+    Halted,
+    TimedOut,
+    Failed,
+}
 
+impl Outcome {
+    // lets scripts tell finished runs from stuck or broken ones.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Outcome::Quit | Outcome::Halted => 0,
+            Outcome::Failed => 1,
+            Outcome::TimedOut => 2,
+        }
+    }
+}
+
+// Settings given on the command line, they win over the ones from the ROM database.
+#[derive(Clone, Copy, Default)]
+pub struct Overrides {
+    pub platform: Option<Platform>,
+    // instructions per second.
+    pub speed: Option<u64>,
+    pub foreground: Option<u32>,
+    pub background: Option<u32>,
+    // nested subroutine calls allowed before the stack overflows.
+    pub stack_depth: Option<usize>,
+    pub memory_stack: bool,
+    // what accesses past the end of memory do.
+    pub memory_access: Option<MemoryAccess>,
+    // run instructions as fast as the VIP did instead of at a fixed speed.
+    pub vip_timing: bool,
+}
+
+// The state the emulator thread and the frontend share.
+#[derive(Clone)]
+pub struct Shared {
+    pub frames: Arc<FrameBuffer>,
+    pub keypad: Arc<Mutex<Keypad>>,
+    // the second keypad of CHIP-8X.
+    pub second_keypad: Arc<Mutex<Keypad>>,
+    pub tone: Arc<Mutex<bool>>,
+    pub quit: Arc<Mutex<bool>>,
+    // host controls the loaded ROM maps onto its keypad.
+    pub bindings: Arc<Mutex<Bindings>>,
+}
+
+impl Shared {
+    pub fn init() -> Self {
+        Self {
+            frames: Arc::new(FrameBuffer::init()),
+            keypad: Arc::new(Mutex::new(Keypad::default())),
+            second_keypad: Arc::new(Mutex::new(Keypad::default())),
+            tone: Arc::new(Mutex::new(false)),
+            quit: Arc::new(Mutex::new(false)),
+            bindings: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+// runs until quit is set or the run ends, then finishes any recordings and sets quit.
+// Without a ROM the menu is shown first.
 pub fn run(
-    rom: String,
-    display_buffer: Arc<Mutex<window::DisplayBuffer>>,
-    key_map: Arc<Mutex<u16>>,
-    debug: bool,
-) {
-    let file: File = File::open(rom).expect("Rom could not be opened.");
-
-    let mut chip = Chip8::init(file);
-    let audio_handler = AudioHandler::init();
-
-    loop {
-        audio_handler.tick(chip.sound_timer.get());
-
-        let instruction = chip.fetch();
-
-        let op_code = (instruction >> 12) & 0xF;
-        let vx = ((instruction >> 8) & 0xF) as usize;
-        let vy = ((instruction >> 4) & 0xF) as usize;
-        let address = instruction & 0xFFF;
-        let value = (instruction & 0xFF) as u8;
-        let short_value = (instruction & 0xF) as u8;
-
-        match op_code {
-            0x0 => match value {
-                0xE0 => chip.op_00e0(&display_buffer),
-                0xEE => chip.op_00ee(),
-                _ => eprintln!("Unmatched instruction: {:04X}", instruction),
-            },
-            0x1 => chip.op_1nnn(address),
-            0x2 => chip.op_2nnn(address),
-            0x3 => {
-                chip.op_3xnn(vx, value);
-            }
-            0x4 => {
-                chip.op_4xnn(vx, value);
+    rom: Option<Rom>,
+    shared: Shared,
+    commands: Receiver<Command>,
+    mut options: Options,
+) -> Outcome {
+    let Shared {
+        frames,
+        keypad,
+        second_keypad,
+        tone,
+        quit,
+        ..
+    } = &shared;
+    let mut game = rom.map(|rom| Game::init(rom, &options, &shared));
+    let mut menu = Menu::scan(&options.rom_dir);
+    let mut audio_recorder = options.record_audio.take();
+    let mut video_recorder = options.record_video.take();
+    // counts emulated time, in instructions a frame has passed every speed / FRAME_RATE of them.
+    // With VIP timing it counts machine cycles instead.
+    let mut frame_progress = 0;
+    // the frame length frame_progress is counted against.
+    let mut progress_units = 0;
+    let mut frame_count = 0;
+    let mut outcome = Outcome::Quit;
+    let mut last_watch = Instant::now();
+
+    'run: while !*quit.lock().unwrap() {
+        for command in commands.try_iter() {
+            match (command, game.as_mut()) {
+                (Command::ToggleVideoRecording, _) => match video_recorder.take() {
+                    Some(recorder) => finish_video_recording(recorder),
+                    None => {
+                        let path = capture::timestamped_path(&options.capture_dir, "gif");
+                        video_recorder = start_video_recording(path, options.capture_scale);
+                    }
+                },
+                (Command::Screenshot, _) => {
+                    let frame = frames.latest();
+                    let path = capture::timestamped_path(&options.capture_dir, "png");
+                    match capture::screenshot(&frame.pixels, options.capture_scale, &path) {
+                        Ok(()) => {
+                            console::report(format!("Screenshot saved to '{}'.", path.display()))
+                        }
+                        Err(e) => console::report(format!(
+                            "Error: Screenshot could not be saved. ({})",
+                            e
+                        )),
+                    }
+                }
+                (Command::Reset, Some(game)) => game.reset(&shared),
+                (Command::HardReset, Some(game)) => {
+                    if let Err(e) = game.reload(&shared) {
+                        console::report(format!("Error: Rom could not be reloaded. ({})", e));
+                    }
+                }
+                (Command::Menu, _) => {
+                    game = None;
+                    shared.bindings.lock().unwrap().clear();
+                    menu = Menu::scan(&options.rom_dir);
+                }
+                (Command::MenuUp, None) => menu.up(),
+                (Command::MenuDown, None) => menu.down(),
+                (Command::MenuSelect, None) => {
+                    if let Some(rom) = menu.selected() {
+                        let source = RomSource::init(rom.to_path_buf(), None);
+                        match Game::load(source, &options, &shared) {
+                            Ok(loaded) => game = Some(loaded),
+                            Err(e) => {
+                                console::report(format!("Error: Rom could not be opened. ({})", e))
+                            }
+                        }
+                    }
+                }
+                _ => {}
             }
-            0x5 => {
-                chip.op_5xy0(vx, vy);
+        }
+
+        if last_watch.elapsed() >= WATCH_INTERVAL {
+            last_watch = Instant::now();
+            if let Some(game) = game.as_mut() {
+                game.poll_watcher(&shared);
             }
-            0x6 => {
-                chip.op_6xnn(vx, value);
+        }
+
+        if let Some(Game {
+            machine,
+            frame,
+            halt_detector,
+            crashed,
+            cycles,
+            ..
+        }) = game.as_mut().filter(|game| !game.crashed)
+        {
+            // the frontend plays the tone for as long as the machine sounds its buzzer.
+            *tone.lock().unwrap() = machine.sound();
+
+            let address = machine.pc();
+            let executed = machine.step(keypad, second_keypad);
+            match executed {
+                Err(fault) => {
+                    console::report(format!("Error: {} at {:#05X}.", fault, address));
+                    *crashed = true;
+                    if options.exit_on_halt {
+                        outcome = Outcome::Failed;
+                        break;
+                    }
+                }
+                Ok(Step {
+                    instruction: Some((address, instruction)),
+                    ..
+                }) if halt_detector.observe(address, instruction, machine.as_ref()) => {
+                    // interactive sessions keep running, the program may still be reset or reloaded.
+                    console::report(format!("Program halted at {:#05X}.", address));
+                    if options.exit_on_halt {
+                        outcome = Outcome::Halted;
+                        break;
+                    }
+                }
+                Ok(_) => {}
             }
-            0x7 => {
-                chip.op_7xnn(vx, value);
+            // every step takes some time, even a failing one, or emulated time would stand still.
+            *cycles = executed.map_or(1, |step| step.cycles.max(1));
+
+            if options.debug {
+                // every step is shown, not just the whole frames.
+                machine.render(frame);
+                frames.publish(frame);
+                if let Ok(Step {
+                    instruction: Some((_, instruction)),
+                    ..
+                }) = executed
+                {
+                    console::print(format!("Instruction: {:04X}", instruction));
+                }
+                console::print(machine.to_string());
+                console::print("Press C to continue.");
+                // one instruction per press, C sits on keypad key B.
+                loop {
+                    let mut keypad = keypad.lock().unwrap();
+                    keypad.latch(Instant::now());
+                    if keypad.was_pressed(0xB) || *quit.lock().unwrap() {
+                        break;
+                    }
+                    drop(keypad);
+                    sleep(DEBUG_POLL_INTERVAL);
+                }
             }
-            0x8 => match short_value {
-                0x0 => chip.op_8xy0(vx, vy),
-                0x1 => chip.op_8xy1(vx, vy),
-                0x2 => chip.op_8xy2(vx, vy),
-                0x3 => chip.op_8xy3(vx, vy),
-                0x4 => chip.op_8xy4(vx, vy),
-                0x5 => chip.op_8xy5(vx, vy),
-                0x6 => chip.op_8xy6(vx, vy),
-                0x7 => chip.op_8xy7(vx, vy),
-                0xE => chip.op_8xye(vx, vy),
-                _ => eprintln!("Unmatched instruction: {:04X}", instruction),
-            },
-            0x9 => {
-                chip.op_9xy0(vx, vy);
+        } else {
+            *tone.lock().unwrap() = false;
+        }
+
+        // what the step just taken cost and what a frame lasts, in the same units. Without a
+        // running game the loop only keeps the frames coming.
+        let (cost, frame_length) = match game.as_ref().filter(|game| !game.crashed) {
+            Some(game) if game.vip.is_some() => (game.cycles, vip::FRAME_CYCLES),
+            Some(game) if game.settings.vip_timing => (game.cycles, vip_timing::FRAME_CYCLES),
+            Some(game) => (FRAME_RATE, game.settings.speed),
+            None => (FRAME_RATE, LOOP_RATE),
+        };
+        let sleep_duration =
+            Duration::from_nanos(1_000_000_000 * cost / (frame_length * FRAME_RATE));
+
+        // progress only carries over while it is counted in the same units.
+        if frame_length != progress_units {
+            frame_progress = 0;
+            progress_units = frame_length;
+        }
+        // a step lasting longer than a frame, like a clear with VIP timing, passes several.
+        frame_progress += cost;
+        while frame_progress >= frame_length {
+            frame_progress -= frame_length;
+            frame_count += 1;
+            let now = Instant::now();
+            keypad.lock().unwrap().latch(now);
+            second_keypad.lock().unwrap().latch(now);
+
+            if options
+                .timeout
+                .is_some_and(|timeout| frame_count > timeout.saturating_mul(FRAME_RATE))
+            {
+                console::report("Timed out.");
+                outcome = Outcome::TimedOut;
+                break 'run;
             }
-            0xA => {
-                chip.op_annn(address);
+
+            // only whole frames are shown, the frontend never sees one being drawn.
+            let frame = match game.as_mut() {
+                Some(game) => {
+                    game.machine.end_frame();
+                    game.machine.render(&mut game.frame);
+                    game.frame
+                }
+                None => menu.render(),
+            };
+            frames.publish(&frame);
+            let sound = game.as_ref().is_some_and(|game| game.machine.sound());
+
+            if let Some(recorder) = audio_recorder.as_mut() {
+                if let Err(e) = recorder.record_frame(sound) {
+                    console::report(format!("Error: Audio recording stopped. ({})", e));
+                    audio_recorder = None;
+                }
             }
-            0xB => {
-                chip.op_bnnn(vx, address);
+
+            if let Some(recorder) = video_recorder.as_mut() {
+                if let Err(e) = recorder.record_frame(&frame.pixels) {
+                    console::report(format!("Error: Video recording stopped. ({})", e));
+                    // what was recorded until then is kept.
+                    if let Some(recorder) = video_recorder.take() {
+                        finish_video_recording(recorder);
+                    }
+                }
             }
-            0xC => {
-                chip.op_cxnn(vx, value);
+        }
+
+        sleep(sleep_duration);
+    }
+
+    if let Some(recorder) = audio_recorder {
+        if let Err(e) = recorder.finalize() {
+            console::report(format!(
+                "Error: Audio recording could not be saved. ({})",
+                e
+            ));
+        }
+    }
+
+    if let Some(recorder) = video_recorder {
+        finish_video_recording(recorder);
+    }
+
+    // the frontend stops with the emulator.
+    *quit.lock().unwrap() = true;
+    outcome
+}
+
+fn execute(
+    chip: &mut Chip8,
+    instruction: u16,
+    keypad: &Mutex<Keypad>,
+    second_keypad: &Mutex<Keypad>,
+) -> Result<(), Fault> {
+    let op_code = (instruction >> 12) & 0xF;
+    let vx = ((instruction >> 8) & 0xF) as usize;
+    let vy = ((instruction >> 4) & 0xF) as usize;
+    let address = instruction & 0xFFF;
+    let value = (instruction & 0xFF) as u8;
+    let short_value = (instruction & 0xF) as u8;
+    // CHIP-8X machines carry the VP-590 colour board, its instructions take the place of others.
+    let chip8x = chip.colour_board.is_some();
+
+    match op_code {
+        0x0 => match value {
+            0xE0 => chip.op_00e0(),
+            0xA0 if chip8x && address == 0x2A0 => chip.op_02a0(),
+            0xEE => return chip.op_00ee(),
+            _ => console::report(format!("Unmatched instruction: {:04X}", instruction)),
+        },
+        0x1 => chip.op_1nnn(address),
+        0x2 => return chip.op_2nnn(address),
+        0x3 => {
+            chip.op_3xnn(vx, value);
+        }
+        0x4 => {
+            chip.op_4xnn(vx, value);
+        }
+        0x5 if chip8x && short_value == 0x1 => chip.op_5xy1(vx, vy),
+        0x5 => {
+            chip.op_5xy0(vx, vy);
+        }
+        0x6 => {
+            chip.op_6xnn(vx, value);
+        }
+        0x7 => {
+            chip.op_7xnn(vx, value);
+        }
+        0x8 => match short_value {
+            0x0 => chip.op_8xy0(vx, vy),
+            0x1 => chip.op_8xy1(vx, vy),
+            0x2 => chip.op_8xy2(vx, vy),
+            0x3 => chip.op_8xy3(vx, vy),
+            0x4 => chip.op_8xy4(vx, vy),
+            0x5 => chip.op_8xy5(vx, vy),
+            0x6 => chip.op_8xy6(vx, vy),
+            0x7 => chip.op_8xy7(vx, vy),
+            0xE => chip.op_8xye(vx, vy),
+            _ => console::report(format!("Unmatched instruction: {:04X}", instruction)),
+        },
+        0x9 => {
+            chip.op_9xy0(vx, vy);
+        }
+        0xA => {
+            chip.op_annn(address);
+        }
+        0xB if chip8x => chip.op_bxyn(vx, vy, short_value),
+        0xB => {
+            chip.op_bnnn(vx, address);
+        }
+        0xC => {
+            chip.op_cxnn(vx, value);
+        }
+        0xD => return chip.op_dxyn(vx, vy, short_value),
+        0xE => match value {
+            0x9E => chip.op_ex9e(vx, &keypad.lock().unwrap()),
+            0xA1 => chip.op_exa1(vx, &keypad.lock().unwrap()),
+            0xF2 if chip8x => chip.op_exf2(vx, &second_keypad.lock().unwrap()),
+            0xF5 if chip8x => chip.op_exf5(vx, &second_keypad.lock().unwrap()),
+            _ => console::report(format!("Unmatched instruction: {:04X}", instruction)),
+        },
+        0xF => match value {
+            0x07 => chip.op_fx07(vx),
+            0x0A => chip.op_fx0a(vx, &keypad.lock().unwrap()),
+            0x15 => chip.op_fx15(vx),
+            0x18 => chip.op_fx18(vx),
+            0x1E => chip.op_fx1e(vx),
+            0x29 => chip.op_fx29(vx),
+            0x33 => return chip.op_fx33(vx),
+            0x55 => return chip.op_fx55(vx),
+            0x65 => return chip.op_fx65(vx),
+            // fxf8 drives the VP-595 sound board, which is not emulated.
+            0xF8 if chip8x => {}
+            0xFB if chip8x => chip.op_fxfb(vx),
+            _ => console::report(format!("Unmatched instruction: {:04X}", instruction)),
+        },
+        _ => console::report(format!("Unmatched instruction: {:04X}", instruction)),
+    }
+
+    Ok(())
+}
+
+// A program read and checked, ready to be played.
+pub struct Rom {
+    source: RomSource,
+    program: Vec<u8>,
+    settings: Settings,
+}
+
+impl Rom {
+    // reads the program and works out how to run it, showing what validation finds on the way.
+    // On the emulated VIP only what its interpreter image does is possible.
+    pub fn load(source: RomSource, overrides: &Overrides, vip: bool) -> Result<Self> {
+        let rom = source.read()?;
+        Self::init(source, rom, overrides, vip)
+    }
+
+    // the same for a ROM already read from its source, as its name and contents.
+    fn init(
+        source: RomSource,
+        rom: (String, Vec<u8>),
+        overrides: &Overrides,
+        vip: bool,
+    ) -> Result<Self> {
+        let (name, contents) = rom;
+        let (program, info) = identify(&name, contents)?;
+        let settings = Settings::resolve(info, rom_source::platform(&name), overrides);
+        check(&program, settings.platform)?;
+        if vip {
+            settings.check_vip()?;
+        }
+
+        Ok(Self {
+            source,
+            program,
+            settings,
+        })
+    }
+}
+
+// the program being played, kept around for resets.
+struct Game {
+    source: RomSource,
+    program: Vec<u8>,
+    machine: Box<dyn Machine>,
+    // the VIP's images when the program runs on one.
+    vip: Option<VipImages>,
+    // the machine's display as of the last frame boundary, what gets published.
+    frame: Frame,
+    watcher: Option<RomWatcher>,
+    overrides: Overrides,
+    settings: Settings,
+    halt_detector: HaltDetector,
+    // stopped by a fault, the last frame stays on screen.
+    crashed: bool,
+    // machine cycles the last step took, with VIP timing or on the VIP.
+    cycles: u64,
+}
+
+// how the program wants to be run, from the ROM database and the command line.
+struct Settings {
+    platform: Platform,
+    quirks: Quirks,
+    stack_depth: usize,
+    memory_access: MemoryAccess,
+    palette: Palette,
+    speed: u64,
+    vip_timing: bool,
+    bindings: Bindings,
+}
+
+impl Game {
+    fn load(source: RomSource, options: &Options, shared: &Shared) -> Result<Self> {
+        let rom = Rom::load(source, &options.overrides, options.vip.is_some())?;
+        Ok(Self::init(rom, options, shared))
+    }
+
+    fn init(rom: Rom, options: &Options, shared: &Shared) -> Self {
+        let Rom {
+            source,
+            program,
+            settings,
+        } = rom;
+        let watcher = source
+            .path()
+            .filter(|_| options.watch)
+            .map(|path| RomWatcher::init(path.to_path_buf()));
+        let machine = build_machine(
+            &program,
+            &settings,
+            &options.overrides,
+            options.vip.as_ref(),
+        );
+        let mut game = Self {
+            machine,
+            vip: options.vip.clone(),
+            settings,
+            source,
+            program,
+            watcher,
+            overrides: options.overrides,
+            frame: Frame::blank(),
+            halt_detector: HaltDetector::init(),
+            crashed: false,
+            cycles: 0,
+        };
+        game.start(shared);
+
+        game
+    }
+
+    // starts the program over on a fresh machine.
+    fn reset(&mut self, shared: &Shared) {
+        self.machine = build_machine(
+            &self.program,
+            &self.settings,
+            &self.overrides,
+            self.vip.as_ref(),
+        );
+        self.start(shared);
+    }
+
+    // what a fresh machine starts with besides itself.
+    fn start(&mut self, shared: &Shared) {
+        *shared.bindings.lock().unwrap() = self.settings.bindings.clone();
+        self.machine.render(&mut self.frame);
+        self.halt_detector = HaltDetector::init();
+        self.crashed = false;
+        self.cycles = 0;
+    }
+
+    // a changed ROM may be a different program, so it is looked up again.
+    fn reload(&mut self, shared: &Shared) -> Result<()> {
+        if self.source == RomSource::Stdin {
+            bail!("Rom was read from stdin, it cannot be read again.");
+        }
+
+        let rom = Rom::load(self.source.clone(), &self.overrides, self.vip.is_some())?;
+        self.replace(rom, shared);
+        Ok(())
+    }
+
+    // the contents the watcher saw change are the ones loaded, the file may have changed again.
+    fn poll_watcher(&mut self, shared: &Shared) {
+        let Some(file) = self.watcher.as_mut().and_then(|watcher| watcher.poll()) else {
+            return;
+        };
+
+        console::report("Rom changed, reloading.");
+        let rom = self.source.open(file).and_then(|rom| {
+            Rom::init(
+                self.source.clone(),
+                rom,
+                &self.overrides,
+                self.vip.is_some(),
+            )
+        });
+        match rom {
+            Ok(rom) => self.replace(rom, shared),
+            Err(e) => console::report(format!("Error: Rom could not be reloaded. ({})", e)),
+        }
+    }
+
+    // starts over with another version of the program.
+    fn replace(&mut self, rom: Rom, shared: &Shared) {
+        self.settings = rom.settings;
+        self.program = rom.program;
+        self.reset(shared);
+    }
+}
+
+// the machine the program runs on, set up as its settings ask.
+fn build_machine(
+    program: &[u8],
+    settings: &Settings,
+    overrides: &Overrides,
+    vip: Option<&VipImages>,
+) -> Box<dyn Machine> {
+    let start = settings.platform.program_start();
+    if let Some(images) = vip {
+        return Box::new(Vip::init(images, program, start, settings.palette));
+    }
+
+    let mut chip = Chip8::init_at(program, start);
+    chip.quirks = settings.quirks;
+    chip.stack = Stack::init(settings.stack_depth);
+    chip.memory_stack = overrides.memory_stack;
+    chip.memory_access = settings.memory_access;
+    chip.palette = settings.palette;
+    if settings.platform == Platform::Chip8x {
+        chip.colour_board = Some(ColourBoard::init());
+    }
+    Box::new(chip)
+}
+
+// turns the contents of a ROM file into the program and what is known about it.
+fn identify(name: &str, contents: Vec<u8>) -> Result<(Vec<u8>, Option<RomInfo>)> {
+    let path = Path::new(name);
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    if extension.eq_ignore_ascii_case("gif") {
+        let title = path.file_stem().unwrap_or_default().to_string_lossy();
+        let (program, info) = cartridge::decode(&contents, &title)?;
+        return Ok((program, Some(info)));
+    }
+
+    let info = database::lookup(&contents);
+    Ok((contents, info))
+}
+
+// shows what is wrong with a program before it runs, failing on what keeps it from running.
+fn check(program: &[u8], platform: Platform) -> Result<()> {
+    let (errors, warnings): (Vec<_>, Vec<_>) = validation::validate(program, platform)
+        .into_iter()
+        .partition(|diagnostic| diagnostic.is_error());
+
+    for warning in warnings {
+        console::report(format!("Warning: {}", warning));
+    }
+    if let Some(error) = errors.first() {
+        bail!("{}", error);
+    }
+
+    Ok(())
+}
+
+impl Settings {
+    // the extension's platform is only used when nothing better is known.
+    fn resolve(info: Option<RomInfo>, default_platform: Platform, overrides: &Overrides) -> Self {
+        if let Some(info) = &info {
+            match info.authors.as_slice() {
+                [] => console::report(format!("Playing '{}'.", info.title)),
+                authors => console::report(format!(
+                    "Playing '{}' by {}.",
+                    info.title,
+                    authors.join(", ")
+                )),
             }
-            0xD => {
-                chip.op_dxyn(vx, vy, short_value, &display_buffer);
+        }
+
+        let recommended = info.as_ref().and_then(|info| info.platform);
+        let platform = overrides
+            .platform
+            .or(recommended)
+            .unwrap_or(default_platform);
+        let mut quirks = platform.quirks();
+        // the database's quirks are only meant for the platform it recommends.
+        if let Some(info) = info.as_ref().filter(|_| recommended == Some(platform)) {
+            for (name, enabled) in &info.quirks {
+                quirks.set(name, *enabled);
             }
-            0xE => match value {
-                0x9E => chip.op_ex9e(vx, &key_map),
-                0xA1 => chip.op_exa1(vx, &key_map),
-                _ => eprintln!("Unmatched instruction: {:04X}", instruction),
-            },
-            0xF => match value {
-                0x07 => chip.op_fx07(vx),
-                0x0A => chip.op_fx0a(vx, &key_map),
-                0x15 => chip.op_fx15(vx),
-                0x18 => chip.op_fx18(vx),
-                0x1E => chip.op_fx1e(vx),
-                0x29 => chip.op_fx29(vx),
-                0x33 => chip.op_fx33(vx),
-                0x55 => chip.op_fx55(vx),
-                0x65 => chip.op_fx65(vx),
-                _ => eprintln!("Unmatched instruction: {:04X}", instruction),
+        }
+
+        let palette = info
+            .as_ref()
+            .and_then(|info| info.palette)
+            .unwrap_or_default();
+        let speed = info
+            .as_ref()
+            .and_then(|info| info.tickrate)
+            .map(|tickrate| tickrate.saturating_mul(FRAME_RATE));
+        let bindings = info
+            .map(|info| {
+                info.keys
+                    .iter()
+                    .filter_map(|(name, key)| Some((Control::from_name(name)?, *key & 0xF)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            platform,
+            quirks,
+            stack_depth: overrides
+                .stack_depth
+                .unwrap_or_else(|| platform.stack_depth()),
+            memory_access: overrides.memory_access.unwrap_or_default(),
+            palette: Palette {
+                background: overrides.background.unwrap_or(palette.background),
+                foreground: overrides.foreground.unwrap_or(palette.foreground),
             },
-            _ => {
-                eprintln!("Unmatched instruction: {:04X}", instruction)
-            }
+            speed: overrides
+                .speed
+                .or(speed)
+                .unwrap_or(LOOP_RATE)
+                .clamp(1, MAX_SPEED),
+            vip_timing: overrides.vip_timing,
+            bindings,
         }
+    }
+}
 
-        if debug {
-            println!("Instruction: {:04X}", instruction);
-            println!("{}", chip);
-            println!("Press C to continue.");
-            loop {
-                let flag = key_map.lock().unwrap();
-                if (*flag >> 11) & 0b1 == 1 {
-                    break;
-                }
-                drop(flag);
-                sleep(SLEEP_DURATION * 10);
+impl Settings {
+    // the emulated VIP runs whatever its interpreter image does, so settings for another
+    // interpreter cannot be honoured there.
+    fn check_vip(&self) -> Result<()> {
+        if self.platform == Platform::Chip8x {
+            bail!("CHIP-8X programs need the colour board, which the emulated VIP lacks. Run it without --vip-interpreter.");
+        }
+        if self.quirks != Platform::OriginalChip8.quirks() {
+            let platform = self.platform.to_possible_value().unwrap();
+            console::report(format!(
+                "Warning: The emulated VIP runs the quirks of its interpreter, the ones of --platform {} and the ROM database are not applied.",
+                platform.get_name()
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+// a failing video recording should not take the game down with it.
+fn start_video_recording(path: PathBuf, scale: usize) -> Option<VideoRecorder> {
+    match VideoRecorder::init(&path, scale) {
+        Ok(recorder) => {
+            console::report(format!("Recording video to '{}'.", path.display()));
+            Some(recorder)
+        }
+        Err(e) => {
+            console::report(format!(
+                "Error: Video recording could not be started. ({})",
+                e
+            ));
+            None
+        }
+    }
+}
+
+fn finish_video_recording(recorder: VideoRecorder) {
+    if let Err(e) = recorder.finalize() {
+        console::report(format!(
+            "Error: Video recording could not be saved. ({})",
+            e
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_win_over_database() {
+        let program = std::fs::read("roms/Pong.ch8").unwrap();
+        let overrides = Overrides {
+            platform: Some(Platform::Superchip),
+            speed: Some(1000),
+            foreground: Some(0xFFAA00),
+            background: None,
+            ..Overrides::default()
+        };
+
+        let recommended = Settings::resolve(
+            database::lookup(&program),
+            Platform::default(),
+            &Overrides::default(),
+        );
+        let settings =
+            Settings::resolve(database::lookup(&program), Platform::default(), &overrides);
+
+        assert_eq!(recommended.quirks, Platform::OriginalChip8.quirks());
+        assert_eq!(recommended.speed, LOOP_RATE);
+        assert_eq!(recommended.bindings.get(&Control::Down), Some(&0x4));
+        assert_eq!(settings.quirks, Platform::Superchip.quirks());
+        assert_eq!(settings.speed, 1000);
+        assert_eq!(settings.palette.foreground, 0xFFAA00);
+        assert_eq!(settings.palette.background, 0x000000);
+        assert_eq!(settings.bindings, recommended.bindings);
+        assert_eq!(recommended.stack_depth, 12);
+        assert_eq!(settings.stack_depth, 32);
+    }
+
+    fn options() -> Options {
+        Options {
+            debug: false,
+            record_audio: None,
+            record_video: None,
+            capture_dir: PathBuf::from("."),
+            capture_scale: 1,
+            watch: false,
+            rom_dir: PathBuf::from("roms"),
+            overrides: Overrides::default(),
+            exit_on_halt: false,
+            timeout: None,
+            vip: None,
+        }
+    }
+
+    #[test]
+    fn test_speed_is_bounded() {
+        let info = RomInfo {
+            title: "Fast".to_string(),
+            authors: Vec::new(),
+            platform: None,
+            quirks: HashMap::new(),
+            tickrate: Some(u64::MAX),
+            keys: HashMap::new(),
+            palette: None,
+        };
+
+        let settings = Settings::resolve(Some(info), Platform::default(), &Overrides::default());
+        assert_eq!(settings.speed, MAX_SPEED);
+    }
+
+    #[test]
+    fn test_check_vip() {
+        let program = vec![0x12, 0x00];
+
+        let rom = |name: &str| {
+            let source = RomSource::init(name.into(), None);
+            let rom = (name.to_string(), program.clone());
+            Rom::init(source, rom, &Overrides::default(), true)
+        };
+
+        assert!(rom("loop.ch8").is_ok());
+        assert!(rom("loop.sc8").is_ok());
+        let error = rom("loop.c8x").err().unwrap().to_string();
+        assert!(error.contains("CHIP-8X"));
+    }
+
+    #[test]
+    fn test_reset() {
+        let shared = Shared::init();
+        let source = RomSource::init("roms/Pong.ch8".into(), None);
+        let mut game = Game::load(source, &options(), &shared).unwrap();
+        let start = game.machine.pc();
+
+        for step in 0..1000 {
+            if step % 10 == 0 {
+                game.machine.end_frame();
             }
+            game.machine
+                .step(&shared.keypad, &shared.second_keypad)
+                .unwrap();
         }
+        game.machine.render(&mut game.frame);
+        game.crashed = true;
+        game.cycles = 40;
+        shared.bindings.lock().unwrap().clear();
+        assert!(game.frame.lit.iter().any(|&lit| lit));
+
+        game.reset(&shared);
+        assert_eq!(game.machine.pc(), start);
+        assert!(game.frame.lit.iter().all(|&lit| !lit));
+        assert!(game.frame.pixels.iter().all(|&pixel| pixel == 0));
+        assert!(!game.crashed);
+        assert_eq!(game.cycles, 0);
+        assert_eq!(*shared.bindings.lock().unwrap(), game.settings.bindings);
+        assert!(!game.settings.bindings.is_empty());
+    }
+
+    #[test]
+    fn test_poll_watcher() {
+        let path = std::env::temp_dir().join("rusty-chip-8-test-poll-watcher.ch8");
+        std::fs::write(&path, [0x12, 0x00]).unwrap();
+        let shared = Shared::init();
+        let options = Options {
+            watch: true,
+            ..options()
+        };
+        let mut game = Game::load(RomSource::init(path.clone(), None), &options, &shared).unwrap();
+
+        // file systems may keep coarse times, so the rewrite is dated explicitly.
+        std::fs::write(&path, [0x00, 0xE0, 0x12, 0x02]).unwrap();
+        let later = std::fs::metadata(&path).unwrap().modified().unwrap() + Duration::from_secs(1);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        game.poll_watcher(&shared);
+        std::fs::remove_file(&path).unwrap();
 
-        sleep(SLEEP_DURATION);
+        assert_eq!(game.program, vec![0x00, 0xE0, 0x12, 0x02]);
     }
 }
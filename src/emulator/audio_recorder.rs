@@ -0,0 +1,95 @@
+use anyhow::Result;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::frontend::audio::tone::{Tone, ToneConfig, ToneControl, SAMPLE_RATE};
+
+use super::FRAME_RATE;
+
+const SAMPLES_PER_FRAME: u32 = SAMPLE_RATE / FRAME_RATE as u32;
+
+// Renders the buzzer into a WAV file frame by frame, so its length follows emulated time.
+pub struct AudioRecorder {
+    writer: WavWriter<BufWriter<File>>,
+    tone: Tone,
+    control: Arc<ToneControl>,
+    frames: u64,
+}
+
+impl AudioRecorder {
+    pub fn init(path: &Path, config: ToneConfig) -> Result<Self> {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let control = Arc::new(ToneControl::default());
+
+        Ok(Self {
+            writer: WavWriter::create(path, spec)?,
+            tone: Tone::init(config, Arc::clone(&control)),
+            control,
+            frames: 0,
+        })
+    }
+
+    pub fn record_frame(&mut self, playing: bool) -> Result<()> {
+        self.control.gate.store(playing, Ordering::Relaxed);
+
+        for sample in self.tone.by_ref().take(SAMPLES_PER_FRAME as usize) {
+            self.writer
+                .write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+        }
+
+        // keep the header up to date every second, so the file stays valid if the process is killed.
+        self.frames += 1;
+        if self.frames.is_multiple_of(FRAME_RATE) {
+            self.writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finalize(self) -> Result<()> {
+        self.writer.finalize()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::WavReader;
+    use std::time::Duration;
+
+    #[test]
+    fn test_record_frame() {
+        let path = std::env::temp_dir().join("rusty-chip-8-test-record-frame.wav");
+        let config = ToneConfig {
+            attack: Duration::ZERO,
+            release: Duration::ZERO,
+            ..ToneConfig::default()
+        };
+
+        let mut recorder = AudioRecorder::init(&path, config).unwrap();
+        recorder.record_frame(false).unwrap();
+        recorder.record_frame(true).unwrap();
+        recorder.record_frame(false).unwrap();
+        recorder.finalize().unwrap();
+
+        let mut reader = WavReader::open(&path).unwrap();
+        let samples: Vec<i16> = reader.samples().map(|sample| sample.unwrap()).collect();
+        std::fs::remove_file(&path).unwrap();
+
+        // three frames of 1/60 of a second each.
+        assert_eq!(samples.len(), 3 * 800);
+        assert!(samples[..800].iter().all(|sample| *sample == 0));
+        assert!(samples[800..1600].iter().any(|sample| *sample != 0));
+        assert!(samples[1600..].iter().all(|sample| *sample == 0));
+    }
+}
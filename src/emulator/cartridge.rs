@@ -0,0 +1,273 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use super::database::RomInfo;
+use super::palette::{parse_color, Palette};
+use super::quirks::Platform;
+
+// Octo shares programs as "cartridge" GIFs. The label is drawn with a 256 colour palette and the
+// low nibble of each pixel's colour index carries half a byte of the payload, high half first.
+// The payload is a 32-bit big endian length followed by that much JSON with the program and
+// Octo's options.
+#[derive(Deserialize)]
+struct Payload {
+    program: Program,
+    #[serde(default)]
+    options: Options,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Program {
+    Bytes(Vec<u8>),
+    Source(String),
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct Options {
+    tickrate: Option<u64>,
+    fill_color: Option<String>,
+    background_color: Option<String>,
+    shift_quirks: bool,
+    load_store_quirks: bool,
+    jump_quirks: bool,
+    logic_quirks: bool,
+    v_blank_quirks: bool,
+    clip_quirks: bool,
+}
+
+// returns the program and its settings, described like a ROM database entry.
+pub fn decode(gif: &[u8], title: &str) -> Result<(Vec<u8>, RomInfo)> {
+    let payload = read_payload(gif)?;
+    let json: String = payload.iter().map(|byte| *byte as char).collect();
+    let payload: Payload =
+        serde_json::from_str(&json).context("Cartridge payload is not valid.")?;
+
+    let program = match payload.program {
+        Program::Bytes(bytes) => bytes,
+        Program::Source(source) => assemble_bytes(&source).with_context(|| {
+            format!(
+                "'{}' is an Octo source cartridge, assemble it first: open it in Octo and \
+                 export it as a .ch8.",
+                title
+            )
+        })?,
+    };
+
+    let options = payload.options;
+    // Octo's quirk switches all start off, which is what the modern platform does.
+    let quirks = HashMap::from([
+        ("shift".to_string(), options.shift_quirks),
+        (
+            "memoryLeaveIUnchanged".to_string(),
+            options.load_store_quirks,
+        ),
+        ("jump".to_string(), options.jump_quirks),
+        ("logic".to_string(), options.logic_quirks),
+        ("vblank".to_string(), options.v_blank_quirks),
+        // Octo wraps sprites unless told to clip them.
+        ("wrap".to_string(), !options.clip_quirks),
+    ]);
+    let palette = match (&options.background_color, &options.fill_color) {
+        (Some(background), Some(foreground)) => Some(Palette {
+            background: parse_color(background).unwrap_or(0x000000),
+            foreground: parse_color(foreground).unwrap_or(0xFFFFFF),
+        }),
+        _ => None,
+    };
+
+    let info = RomInfo {
+        title: title.to_string(),
+        authors: Vec::new(),
+        platform: Some(Platform::ModernChip8),
+        quirks,
+        tickrate: options.tickrate,
+        keys: HashMap::new(),
+        palette,
+    };
+
+    Ok((program, info))
+}
+
+fn read_payload(gif: &[u8]) -> Result<Vec<u8>> {
+    let mut decoder = gif::DecodeOptions::new()
+        .read_info(gif)
+        .context("Cartridge is not a GIF.")?;
+
+    let mut nibbles = Vec::new();
+    while let Some(frame) = decoder.read_next_frame()? {
+        nibbles.extend(frame.buffer.iter().map(|index| index & 0xF));
+    }
+
+    let bytes: Vec<u8> = nibbles
+        .chunks_exact(2)
+        .map(|pair| (pair[0] << 4) | pair[1])
+        .collect();
+    let Some((length, rest)) = bytes.split_first_chunk::<4>() else {
+        bail!("Cartridge holds no program.");
+    };
+    let length = u32::from_be_bytes(*length) as usize;
+    if length > rest.len() {
+        bail!("Cartridge holds no program.");
+    }
+
+    Ok(rest[..length].to_vec())
+}
+
+// Cartridges carry Octo source, which needs Octo to assemble. Programs that are only numbers and
+// labels, like the ones Octo makes when opening a .ch8, can be read without it.
+fn assemble_bytes(source: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut tokens = source
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(str::split_whitespace);
+
+    while let Some(token) = tokens.next() {
+        // labels only name addresses, the bytes stay in order.
+        if token == ":" {
+            tokens.next();
+            continue;
+        }
+
+        let byte = if let Some(hex) = token.strip_prefix("0x") {
+            u8::from_str_radix(hex, 16)
+        } else if let Some(binary) = token.strip_prefix("0b") {
+            u8::from_str_radix(binary, 2)
+        } else {
+            token.parse()
+        };
+        match byte {
+            Ok(byte) => bytes.push(byte),
+            Err(_) => bail!("'{}' is not a byte.", token),
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // draws the payload the way Octo does, two pixels per byte.
+    fn cartridge(json: &str) -> Vec<u8> {
+        let mut payload = (json.len() as u32).to_be_bytes().to_vec();
+        payload.extend(json.bytes());
+        let mut pixels: Vec<u8> = payload
+            .iter()
+            .flat_map(|byte| [0x30 | (byte >> 4), 0x30 | (byte & 0xF)])
+            .collect();
+        pixels.resize(pixels.len().next_multiple_of(64), 0);
+
+        let palette: Vec<u8> = (0..=255).flat_map(|index| [index, index, index]).collect();
+        let height = (pixels.len() / 64) as u16;
+        let mut gif = Vec::new();
+        let mut encoder = gif::Encoder::new(&mut gif, 64, height, &palette).unwrap();
+        let frame = gif::Frame {
+            width: 64,
+            height,
+            buffer: pixels.into(),
+            ..gif::Frame::default()
+        };
+        encoder.write_frame(&frame).unwrap();
+        drop(encoder);
+
+        gif
+    }
+
+    #[test]
+    fn test_decode() {
+        let gif = cartridge(
+            r##"{"program": ": main 0x12 0x00 # loop forever\n 0b1010 10",
+                "options": {"tickrate": 20, "fillColor": "#FFAA00", "backgroundColor": "#112233",
+                            "shiftQuirks": true, "logicQuirks": false}}"##,
+        );
+
+        let (program, info) = decode(&gif, "Loop").unwrap();
+
+        assert_eq!(program, vec![0x12, 0x00, 0b1010, 10]);
+        assert_eq!(info.title, "Loop");
+        assert_eq!(info.tickrate, Some(20));
+        assert_eq!(info.quirks.get("shift"), Some(&true));
+        assert_eq!(info.quirks.get("logic"), Some(&false));
+        assert_eq!(
+            info.palette,
+            Some(Palette {
+                background: 0x112233,
+                foreground: 0xFFAA00,
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_source() {
+        let gif = cartridge(r#"{"program": ": main\n  v0 := 1\n  loop again"}"#);
+
+        assert!(decode(&gif, "Source").is_err());
+    }
+
+    // what Octo exports: 128x64 frames whose colour indices keep the label in the high nibble and
+    // the payload in the low one, the last frame padded out.
+    fn octo_export(json: &str) -> Vec<u8> {
+        const FRAME: usize = 128 * 64;
+        let mut payload = (json.len() as u32).to_be_bytes().to_vec();
+        payload.extend(json.bytes());
+        let mut nibbles: Vec<u8> = payload
+            .iter()
+            .flat_map(|byte| [byte >> 4, byte & 0xF])
+            .collect();
+        nibbles.resize(nibbles.len().next_multiple_of(FRAME), 0);
+
+        let palette: Vec<u8> = (0..=255).flat_map(|index| [index, 0, index]).collect();
+        let mut gif = Vec::new();
+        let mut encoder = gif::Encoder::new(&mut gif, 128, 64, &palette).unwrap();
+        for frame in nibbles.chunks(FRAME) {
+            // a striped label, as if drawn over the payload.
+            let pixels: Vec<u8> = frame
+                .iter()
+                .enumerate()
+                .map(|(pixel, nibble)| ((pixel / 128 % 4) as u8) << 4 | nibble)
+                .collect();
+            let frame = gif::Frame {
+                width: 128,
+                height: 64,
+                buffer: pixels.into(),
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&frame).unwrap();
+        }
+        drop(encoder);
+
+        gif
+    }
+
+    #[test]
+    fn test_octo_export() {
+        // Octo's default options, and source long enough to need a second frame.
+        let source = format!(
+            ": main\n  clear\n  i := logo\n  sprite v0 v1 5\n  loop again\n: logo 0xF0 0x90 0xF0{}",
+            "\n# padding".repeat(500)
+        );
+        let json = format!(
+            r##"{{"program":{},"options":{{"tickrate":20,"fillColor":"#FFCC00","fillColor2":"#FF6600","blendColor":"#662200","backgroundColor":"#996600","buzzColor":"#FFAA00","quietColor":"#000000","shiftQuirks":false,"loadStoreQuirks":false,"vfOrderQuirks":false,"clipQuirks":false,"vBlankQuirks":false,"jumpQuirks":false,"screenRotation":0,"maxSize":3216,"touchInputMode":"none","logicQuirks":false,"fontStyle":"octo"}}}}"##,
+            serde_json::to_string(&source).unwrap()
+        );
+        assert!(json.len() * 2 > 128 * 64);
+        let gif = octo_export(&json);
+
+        // the length prefix ends the payload before the padding, the label does not get in.
+        let payload = read_payload(&gif).unwrap();
+        assert_eq!(payload, json.as_bytes());
+        let payload: Payload = serde_json::from_slice(&payload).unwrap();
+        assert!(matches!(payload.program, Program::Source(program) if program == source));
+        assert_eq!(payload.options.tickrate, Some(20));
+        assert_eq!(payload.options.fill_color.as_deref(), Some("#FFCC00"));
+        assert_eq!(payload.options.background_color.as_deref(), Some("#996600"));
+
+        let error = decode(&gif, "Logo").unwrap_err().to_string();
+        assert!(error.contains("'Logo' is an Octo source cartridge, assemble it first"));
+    }
+}
@@ -1,63 +1,192 @@
 use std::fmt;
 use std::num::Wrapping;
-use std::sync::{Arc, Mutex};
 
+use colour_board::ColourBoard;
 use rand::random;
+use stack::Stack;
 use timer::Timer;
 
-use crate::window;
+use super::keypad::Keypad;
+use super::palette::Palette;
+use super::quirks::{MemoryAccess, Quirks};
+use super::{DisplayBuffer, HEIGHT, WIDTH};
 
-mod fonts;
+pub(super) mod colour_board;
+pub(super) mod fonts;
+pub(super) mod stack;
 mod timer;
 
-const MEMORY_SIZE: usize = 4096;
+pub(super) const MEMORY_SIZE: usize = 4096;
 pub(crate) type Memory = [u8; MEMORY_SIZE];
-type Stack = Vec<u16>;
 type Instruction = u16;
+// where the VIP interpreter keeps the return addresses of subroutines.
+const STACK_START: usize = 0xEA0;
+const DEFAULT_STACK_DEPTH: usize = 16;
+// length of the beep fx0a gives on a key press.
+const KEY_BEEP_FRAMES: u8 = 4;
+
+// Errors a program can run into, they stop emulation like they would crash a real machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    StackOverflow,
+    StackUnderflow,
+    // with the fault memory access policy.
+    MemoryOutOfBounds { address: usize },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::StackOverflow => write!(f, "Stack overflow, too many nested subroutine calls"),
+            Fault::StackUnderflow => write!(f, "Can't return from top level"),
+            Fault::MemoryOutOfBounds { address } => {
+                write!(f, "Memory address {:#05X} is out of bounds", address)
+            }
+        }
+    }
+}
 
 pub struct Chip8 {
     pub memory: Memory,
     pub pc: usize,
     pub index_register: u16,
-    pub stack: Vec<u16>,
+    pub stack: Stack,
+    // keep return addresses in memory at STACK_START too, for programs that look at them.
+    pub memory_stack: bool,
+    pub memory_access: MemoryAccess,
     pub delay_timer: Timer,
     pub sound_timer: Timer,
     pub registers: [u8; 0x10],
+    pub quirks: Quirks,
+    pub palette: Palette,
+    // the VP-590 of CHIP-8X, which colours the display instead of the palette.
+    pub colour_board: Option<ColourBoard>,
+    // which pixels are lit, colours only come in when the display is rendered.
+    pub lit: [bool; WIDTH * HEIGHT],
+    // set while fx0a is waiting for a key.
+    key_wait: Option<KeyWait>,
+    // set while dxyn waits for the next frame, true once it has begun.
+    vblank_wait: Option<bool>,
+}
+
+// Keys held when fx0a started waiting do not count until they are let go.
+#[derive(Clone, Copy, Debug)]
+struct KeyWait {
+    held: u16,
+    pressed: Option<u8>,
 }
 
 impl Chip8 {
+    // the machine is set up for its platform where the game starts, tests mostly need 0x200.
+    #[cfg(test)]
     pub fn init(rom: impl std::io::Read) -> Self {
+        Self::init_at(rom, PROGRAM_START)
+    }
+
+    // for platforms loading programs somewhere else than 0x200.
+    pub fn init_at(rom: impl std::io::Read, start: usize) -> Self {
         let mut memory = [0; MEMORY_SIZE];
 
         load_fonts(&mut memory);
-        load_program(&mut memory, rom);
+        load_program(&mut memory, rom, start);
 
         Self {
             memory,
-            pc: PROGRAM_START,
+            pc: start,
             index_register: 0x0,
-            stack: Stack::new(),
+            stack: Stack::init(DEFAULT_STACK_DEPTH),
+            memory_stack: false,
+            memory_access: MemoryAccess::default(),
             delay_timer: Timer::init(),
             sound_timer: Timer::init(),
             registers: [0x0; 0x10],
+            quirks: Quirks::default(),
+            palette: Palette::default(),
+            colour_board: None,
+            lit: [false; WIDTH * HEIGHT],
+            key_wait: None,
+            vblank_wait: None,
         }
     }
 
-    pub fn fetch(&mut self) -> Instruction {
-        let inst = u16::from_be_bytes([self.memory[self.pc], self.memory[self.pc + 1]]);
+    pub fn fetch(&mut self) -> Result<Instruction, Fault> {
+        let inst = u16::from_be_bytes([self.read(self.pc)?, self.read(self.pc + 1)?]);
         self.pc += 2;
-        inst
+        Ok(inst)
+    }
+
+    // every op goes through read and write, so addresses past the end follow the policy.
+    pub fn read(&self, address: usize) -> Result<u8, Fault> {
+        Ok(self.memory[self.resolve(address)?])
+    }
+
+    pub fn write(&mut self, address: usize, value: u8) -> Result<(), Fault> {
+        self.memory[self.resolve(address)?] = value;
+        Ok(())
+    }
+
+    fn resolve(&self, address: usize) -> Result<usize, Fault> {
+        match self.memory_access {
+            MemoryAccess::Wrap => Ok(address % MEMORY_SIZE),
+            MemoryAccess::Clamp => Ok(address.min(MEMORY_SIZE - 1)),
+            MemoryAccess::Fault if address < MEMORY_SIZE => Ok(address),
+            MemoryAccess::Fault => Err(Fault::MemoryOutOfBounds { address }),
+        }
+    }
+
+    // shows the lit pixels in the palette's colours, or the colour board's.
+    pub fn render(&self, display_buffer: &mut DisplayBuffer) {
+        match &self.colour_board {
+            Some(board) => board.render(&self.lit, display_buffer),
+            None => {
+                for (colour, &lit) in display_buffer.iter_mut().zip(self.lit.iter()) {
+                    *colour = if lit {
+                        self.palette.foreground
+                    } else {
+                        self.palette.background
+                    };
+                }
+            }
+        }
+    }
+
+    // whether dxyn is still waiting for the frame to end, having drawn nothing yet.
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.vblank_wait.is_some()
+    }
+
+    // called once per emulated frame.
+    pub fn tick_timers(&mut self) {
+        self.delay_timer.tick();
+        self.sound_timer.tick();
+        if let Some(vblank) = self.vblank_wait.as_mut() {
+            *vblank = true;
+        }
     }
 
     // clear screen.
-    pub fn op_00e0(&mut self, display_buffer: &Arc<Mutex<window::DisplayBuffer>>) {
-        let mut display_buffer = display_buffer.lock().unwrap();
-        *display_buffer = [0u32; window::WIDTH * window::HEIGHT];
+    pub fn op_00e0(&mut self) {
+        self.lit = [false; WIDTH * HEIGHT];
     }
 
-    // return from subroutine.
-    pub fn op_00ee(&mut self) {
-        self.pc = self.stack.pop().expect("Can't return from top level") as usize;
+    // cycle the background colour of the CHIP-8X colour board.
+    pub fn op_02a0(&mut self) {
+        if let Some(board) = self.colour_board.as_mut() {
+            board.cycle_background();
+        }
+    }
+
+    // return from subroutine. With the stack in memory the address is read from there.
+    pub fn op_00ee(&mut self) -> Result<(), Fault> {
+        let depth = self.stack.entries().len();
+        let address = self.stack.pop()?;
+        self.pc = if self.memory_stack {
+            let slot = STACK_START + (depth - 1) * 2;
+            u16::from_be_bytes([self.read(slot)?, self.read(slot + 1)?]) as usize
+        } else {
+            address as usize
+        };
+        Ok(())
     }
 
     // jump, sets program counter to the given address.
@@ -66,9 +195,16 @@ impl Chip8 {
     }
 
     // call subroutine.
-    pub fn op_2nnn(&mut self, address: u16) {
-        self.stack.push(self.pc as u16);
+    pub fn op_2nnn(&mut self, address: u16) -> Result<(), Fault> {
+        self.stack.push(self.pc as u16)?;
+        if self.memory_stack {
+            let slot = STACK_START + (self.stack.entries().len() - 1) * 2;
+            let [high, low] = (self.pc as u16).to_be_bytes();
+            self.write(slot, high)?;
+            self.write(slot + 1, low)?;
+        }
         self.pc = address as usize;
+        Ok(())
     }
 
     // skip next instruction if vx register equals given value
@@ -92,6 +228,12 @@ impl Chip8 {
         }
     }
 
+    // add the nibbles of vy to the ones of vx, without carrying from one to the other.
+    pub fn op_5xy1(&mut self, vx: usize, vy: usize) {
+        let (x, y) = (self.registers[vx], self.registers[vy]);
+        self.registers[vx] = (x & 0xF0).wrapping_add(y & 0xF0) | (x.wrapping_add(y) & 0x0F);
+    }
+
     pub fn op_6xnn(&mut self, vx: usize, value: u8) {
         self.registers[vx] = value
     }
@@ -109,19 +251,25 @@ impl Chip8 {
     // binary or, resets vf based on https://github.com/Timendus/chip8-test-suite?tab=readme-ov-file#quirks-test
     pub fn op_8xy1(&mut self, vx: usize, vy: usize) {
         self.registers[vx] |= self.registers[vy];
-        self.registers[0xF] = 0x0;
+        if self.quirks.logic {
+            self.registers[0xF] = 0x0;
+        }
     }
 
     // binary and, resets vf based on https://github.com/Timendus/chip8-test-suite?tab=readme-ov-file#quirks-test
     pub fn op_8xy2(&mut self, vx: usize, vy: usize) {
         self.registers[vx] &= self.registers[vy];
-        self.registers[0xF] = 0x0;
+        if self.quirks.logic {
+            self.registers[0xF] = 0x0;
+        }
     }
 
     // binary xor,  resets vf based on https://github.com/Timendus/chip8-test-suite?tab=readme-ov-file#quirks-test
     pub fn op_8xy3(&mut self, vx: usize, vy: usize) {
         self.registers[vx] ^= self.registers[vy];
-        self.registers[0xF] = 0x0;
+        if self.quirks.logic {
+            self.registers[0xF] = 0x0;
+        }
     }
 
     // add registers together, with overflow.
@@ -150,8 +298,9 @@ impl Chip8 {
 
     // shift right, put the shifted out bit into vf.
     pub fn op_8xy6(&mut self, vx: usize, vy: usize) {
-        let right_bit = self.registers[vy] & 0b1;
-        (self.registers[vx], _) = self.registers[vy].overflowing_shr(1);
+        let source = if self.quirks.shift { vx } else { vy };
+        let right_bit = self.registers[source] & 0b1;
+        (self.registers[vx], _) = self.registers[source].overflowing_shr(1);
         self.registers[0xF] = right_bit;
     }
 
@@ -169,8 +318,9 @@ impl Chip8 {
 
     // shift left, put the shifted out bit into vf.
     pub fn op_8xye(&mut self, vx: usize, vy: usize) {
-        let left_bit = (self.registers[vy] >> 7) & 0b1;
-        (self.registers[vx], _) = self.registers[vy].overflowing_shl(1);
+        let source = if self.quirks.shift { vx } else { vy };
+        let left_bit = (self.registers[source] >> 7) & 0b1;
+        (self.registers[vx], _) = self.registers[source].overflowing_shl(1);
         self.registers[0xF] = left_bit;
     }
 
@@ -186,39 +336,61 @@ impl Chip8 {
         self.index_register = address;
     }
 
-    // jump with offset
-    pub fn op_bnnn(&mut self, _vx: usize, address: u16) {
-        let offset = self.registers[0x0];
+    // jump with offset, the jump quirk takes the offset from vx instead of v0.
+    pub fn op_bnnn(&mut self, vx: usize, address: u16) {
+        let offset = self.registers[if self.quirks.jump { vx } else { 0x0 }];
         self.pc = address as usize + offset as usize;
     }
 
+    // colour part of the CHIP-8X display. bxy0 colours zones of 8x4 pixels: vx holds the first
+    // column in its low nibble and how many more follow in the high one, vx + 1 the same for the
+    // rows and vy the colour. bxyn colours n rows from the one in vx + 1, in the column of the
+    // pixel at vx, with the colour in vy.
+    pub fn op_bxyn(&mut self, vx: usize, vy: usize, n: u8) {
+        let Some(board) = self.colour_board.as_mut() else {
+            return;
+        };
+
+        let (x, next) = (self.registers[vx] as usize, self.registers[(vx + 1) & 0xF]);
+        if n == 0 {
+            let y = next as usize;
+            let columns = (x & 0xF)..(x & 0xF) + (x >> 4) + 1;
+            let rows = (y & 0xF) * 4..((y & 0xF) + (y >> 4) + 1) * 4;
+            board.set_colour(columns, rows, self.registers[vy]);
+        } else {
+            let column = x / colour_board::ZONE_WIDTH;
+            let y = next as usize;
+            board.set_colour(column..column + 1, y..y + n as usize, self.registers[vy]);
+        }
+    }
+
     // random
     pub fn op_cxnn(&mut self, vx: usize, value: u8) {
         self.registers[vx] = random::<u8>() & value
     }
 
     // display
-    pub fn op_dxyn(
-        &mut self,
-        vx: usize,
-        vy: usize,
-        num_of_rows: u8,
-        display_buffer: &Arc<Mutex<window::DisplayBuffer>>,
-    ) {
-        let x = self.registers[vx] & (window::WIDTH - 1) as u8;
-        let y = self.registers[vy] & (window::HEIGHT - 1) as u8;
-
-        let mut display_buffer = display_buffer.lock().unwrap();
+    pub fn op_dxyn(&mut self, vx: usize, vy: usize, num_of_rows: u8) -> Result<(), Fault> {
+        // the instruction is repeated until the frame is over, so the wait passes in emulated time.
+        if self.quirks.vblank && self.vblank_wait.take() != Some(true) {
+            self.vblank_wait = Some(false);
+            self.pc -= 2;
+            return Ok(());
+        }
+
+        let x = self.registers[vx] as usize & (WIDTH - 1);
+        let y = self.registers[vy] as usize & (HEIGHT - 1);
 
+        // sprites are cut off at the edges, or come back on the other side with the wrap quirk.
         self.registers[0xF] = 0;
-        for y_offset in 0..num_of_rows {
-            if y + y_offset >= window::HEIGHT as u8 {
+        for y_offset in 0..num_of_rows as usize {
+            if y + y_offset >= HEIGHT && !self.quirks.wrap {
                 break;
             }
 
-            let sprite_row_slice = self.memory[self.index_register as usize + y_offset as usize];
+            let sprite_row_slice = self.read(self.index_register as usize + y_offset)?;
             for x_offset in 0..8 {
-                if x + x_offset >= window::WIDTH as u8 {
+                if x + x_offset >= WIDTH && !self.quirks.wrap {
                     break;
                 }
 
@@ -227,30 +399,42 @@ impl Chip8 {
                     continue;
                 }
 
-                let current_pixel =
-                    (y + y_offset) as usize * window::WIDTH + (x + x_offset) as usize;
+                let current_pixel = (y + y_offset) % HEIGHT * WIDTH + (x + x_offset) % WIDTH;
 
-                if display_buffer[current_pixel] == 0xFFFFFF {
+                if self.lit[current_pixel] {
                     self.registers[0xF] = 0x1;
                 }
-
-                display_buffer[current_pixel] ^= 0xFFFFFF;
+                self.lit[current_pixel] = !self.lit[current_pixel];
             }
         }
+
+        Ok(())
     }
 
     // skip if key is pressed
-    pub fn op_ex9e(&mut self, vx: usize, key_map: &Arc<Mutex<u16>>) {
-        let flag = key_map.lock().unwrap();
-        if (*flag >> self.registers[vx]) & 0b1 == 1 {
+    pub fn op_ex9e(&mut self, vx: usize, keypad: &Keypad) {
+        if keypad.is_down(self.registers[vx]) {
             self.pc += 2;
         }
     }
 
     // skip if key is not pressed
-    pub fn op_exa1(&mut self, vx: usize, key_map: &Arc<Mutex<u16>>) {
-        let flag = key_map.lock().unwrap();
-        if (*flag >> self.registers[vx]) & 0b1 == 0 {
+    pub fn op_exa1(&mut self, vx: usize, keypad: &Keypad) {
+        if !keypad.is_down(self.registers[vx]) {
+            self.pc += 2;
+        }
+    }
+
+    // skip if key is pressed on the second keypad of CHIP-8X
+    pub fn op_exf2(&mut self, vx: usize, second_keypad: &Keypad) {
+        if second_keypad.is_down(self.registers[vx]) {
+            self.pc += 2;
+        }
+    }
+
+    // skip if key is not pressed on the second keypad of CHIP-8X
+    pub fn op_exf5(&mut self, vx: usize, second_keypad: &Keypad) {
+        if !second_keypad.is_down(self.registers[vx]) {
             self.pc += 2;
         }
     }
@@ -260,15 +444,32 @@ impl Chip8 {
         self.registers[vx] = self.delay_timer.get();
     }
 
-    // block until a key is pressed, set it to vx.
-    pub fn op_fx0a(&mut self, vx: usize, key_map: &Arc<Mutex<u16>>) {
-        let flag = key_map.lock().unwrap();
-        if *flag == 0x00 {
-            self.pc -= 2;
-        } else {
-            // taking the most significant bit as the pressed button.
-            let val = flag.ilog(2);
-            self.registers[vx] = val as u8;
+    // block until a key is pressed, or pressed and released with the key release quirk,
+    // set it to vx.
+    pub fn op_fx0a(&mut self, vx: usize, keypad: &Keypad) {
+        let keys = keypad.keys();
+        let wait = self.key_wait.get_or_insert(KeyWait {
+            held: keys,
+            pressed: None,
+        });
+        wait.held &= keys;
+
+        let new_keys = keys & !wait.held;
+        if wait.pressed.is_none() && new_keys != 0 {
+            // taking the lowest of the keys pressed at once.
+            wait.pressed = Some(new_keys.trailing_zeros() as u8);
+            if self.quirks.key_beep {
+                self.sound_timer
+                    .set(self.sound_timer.get().max(KEY_BEEP_FRAMES));
+            }
+        }
+
+        match wait.pressed {
+            Some(key) if !self.quirks.key_release || !keypad.is_down(key) => {
+                self.registers[vx] = key;
+                self.key_wait = None;
+            }
+            _ => self.pc -= 2,
         }
     }
 
@@ -303,35 +504,59 @@ impl Chip8 {
     }
 
     // binary-coded decimal conversion
-    pub fn op_fx33(&mut self, vx: usize) {
+    pub fn op_fx33(&mut self, vx: usize) -> Result<(), Fault> {
         let value = self.registers[vx];
+        let address = self.index_register as usize;
 
         let first_digit = value / 100;
-        self.memory[self.index_register as usize] = first_digit;
+        self.write(address, first_digit)?;
 
         let second_digit = (value / 10) % 10;
-        self.memory[self.index_register as usize + 1] = second_digit;
+        self.write(address + 1, second_digit)?;
 
         let third_digit = (value % 100) % 10;
-        self.memory[self.index_register as usize + 2] = third_digit;
+        self.write(address + 2, third_digit)
     }
 
     // save to memory
-    pub fn op_fx55(&mut self, vx: usize) {
+    pub fn op_fx55(&mut self, vx: usize) -> Result<(), Fault> {
         for current_reg in 0..vx + 1 {
-            self.memory[self.index_register as usize + current_reg] = self.registers[current_reg];
+            let address = self.index_register as usize + current_reg;
+            self.write(address, self.registers[current_reg])?;
         }
 
-        self.index_register += vx as u16 + 1;
+        self.advance_index(vx);
+        Ok(())
     }
 
     // load from memory
-    pub fn op_fx65(&mut self, vx: usize) {
+    pub fn op_fx65(&mut self, vx: usize) -> Result<(), Fault> {
         for current_reg in 0..vx + 1 {
-            self.registers[current_reg] = self.memory[self.index_register as usize + current_reg];
+            self.registers[current_reg] = self.read(self.index_register as usize + current_reg)?;
         }
 
-        self.index_register += vx as u16 + 1;
+        self.advance_index(vx);
+        Ok(())
+    }
+
+    // input from the CHIP-8X I/O port. Nothing is attached to it, so it reads 0 instead of waiting
+    // for a byte forever.
+    pub fn op_fxfb(&mut self, vx: usize) {
+        self.registers[vx] = 0;
+    }
+
+    // where fx55 and fx65 leave I depends on the interpreter.
+    fn advance_index(&mut self, vx: usize) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+
+        let increment = if self.quirks.memory_increment_by_x {
+            vx
+        } else {
+            vx + 1
+        };
+        self.index_register = self.index_register.wrapping_add(increment as u16);
     }
 }
 
@@ -346,20 +571,21 @@ impl fmt::Display for Chip8 {
             self.sound_timer.get()
         )?;
         writeln!(f, "Registers: {:?}", self.registers)?;
-        writeln!(f, "Stack: {:?}", self.stack)?;
+        writeln!(f, "Stack: {:?}", self.stack.entries())?;
 
         Ok(())
     }
 }
 
-const PROGRAM_START: usize = 0x200;
+#[cfg(test)]
+pub(super) const PROGRAM_START: usize = 0x200;
 
-fn load_program(memory: &mut Memory, mut rom: impl std::io::Read) {
+fn load_program(memory: &mut Memory, mut rom: impl std::io::Read, start: usize) {
     let mut buffer = Vec::new();
     rom.read_to_end(&mut buffer).expect("Failed to read ROM");
 
-    let start = PROGRAM_START;
-    let end = PROGRAM_START + buffer.len().min(memory.len() - PROGRAM_START);
+    // programs that do not fit are turned down by validation before they get here.
+    let end = start + buffer.len().min(memory.len() - start);
     memory[start..end].copy_from_slice(&buffer[..(end - start)]);
 }
 
@@ -370,8 +596,22 @@ fn load_fonts(memory: &mut Memory) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::emulator::quirks::Platform;
     use std::io::Cursor;
 
+    fn keypad(keys: u16) -> Keypad {
+        let mut keypad = Keypad::default();
+        set_keys(&mut keypad, keys);
+        keypad
+    }
+
+    // holds the given keys for a frame.
+    fn set_keys(keypad: &mut Keypad, keys: u16) {
+        let now = std::time::Instant::now();
+        keypad.report(keys, now);
+        keypad.latch(now);
+    }
+
     #[test]
     fn test_load_rom() {
         let rom_data = vec![0xAA, 0xBB, 0xCC];
@@ -384,36 +624,53 @@ mod tests {
     }
 
     #[test]
-    fn test_op_00e0() {
-        use std::sync::{Arc, Mutex};
+    fn test_tick_timers() {
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.delay_timer.set(2);
+        emulator.sound_timer.set(1);
+
+        emulator.tick_timers();
+        assert_eq!(emulator.delay_timer.get(), 1);
+        assert_eq!(emulator.sound_timer.get(), 0);
 
-        let display_buffer = Arc::new(Mutex::new([0xFFFFFFFF; window::WIDTH * window::HEIGHT]));
+        emulator.tick_timers();
+        assert_eq!(emulator.delay_timer.get(), 0);
+        assert_eq!(emulator.sound_timer.get(), 0);
+    }
+
+    #[test]
+    fn test_op_00e0() {
+        let mut display_buffer = [0xFFFFFFFF; WIDTH * HEIGHT];
         let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.lit = [true; WIDTH * HEIGHT];
 
-        emulator.op_00e0(&display_buffer);
+        emulator.op_00e0();
+        emulator.render(&mut display_buffer);
 
-        let buffer = display_buffer.lock().unwrap();
-        let expected_result = [0x0; window::WIDTH * window::HEIGHT];
+        let expected_result = [0x0; WIDTH * HEIGHT];
 
-        assert_eq!(*buffer, expected_result);
+        assert_eq!(display_buffer, expected_result);
     }
 
     #[test]
     fn test_op_00ee() {
         let mut emulator = Chip8::init(Cursor::new(vec![]));
-        emulator.stack.push(0x200);
+        emulator.stack.push(0x200).unwrap();
 
-        emulator.op_00ee();
+        emulator.op_00ee().unwrap();
 
         assert_eq!(emulator.pc, 0x200);
     }
 
     #[test]
-    #[should_panic(expected = "Can't return from top level")]
     fn test_op_00ee_empty_stack() {
         let mut emulator = Chip8::init(Cursor::new(vec![]));
 
-        emulator.op_00ee();
+        assert_eq!(emulator.op_00ee(), Err(Fault::StackUnderflow));
+        assert_eq!(
+            Fault::StackUnderflow.to_string(),
+            "Can't return from top level"
+        );
     }
 
     #[test]
@@ -430,10 +687,58 @@ mod tests {
         let mut emulator = Chip8::init(Cursor::new(vec![]));
         emulator.pc = 0x200;
 
-        emulator.op_2nnn(0x400); // Call subroutine at address 0x400
+        emulator.op_2nnn(0x400).unwrap(); // Call subroutine at address 0x400
 
         assert_eq!(emulator.pc, 0x400); // Ensure PC jumps to new address
-        assert_eq!(emulator.stack.last(), Some(&0x200)); // Ensure the previous PC is stored in the stack
+        assert_eq!(emulator.stack.entries().last(), Some(&0x200)); // Ensure the previous PC is stored in the stack
+    }
+
+    #[test]
+    fn test_op_2nnn_stack_overflow() {
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.stack = Stack::init(12);
+
+        for _ in 0..12 {
+            emulator.op_2nnn(0x200).unwrap();
+        }
+
+        assert_eq!(emulator.op_2nnn(0x200), Err(Fault::StackOverflow));
+    }
+
+    #[test]
+    fn test_memory_stack() {
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.memory_stack = true;
+        emulator.pc = 0x234;
+
+        emulator.op_2nnn(0x400).unwrap();
+        assert_eq!(emulator.memory[STACK_START..STACK_START + 2], [0x02, 0x34]);
+
+        // a program changing its return address in memory returns there.
+        emulator.memory[STACK_START + 1] = 0x50;
+        emulator.op_00ee().unwrap();
+        assert_eq!(emulator.pc, 0x250);
+    }
+
+    #[test]
+    fn test_memory_stack_with_memory_access() {
+        for memory_access in [MemoryAccess::Fault, MemoryAccess::Clamp] {
+            let mut emulator = Chip8::init(Cursor::new(vec![]));
+            emulator.memory_stack = true;
+            emulator.memory_access = memory_access;
+            emulator.stack = Stack::init(64);
+
+            // the deepest stack there is still fits in memory, nothing faults or gets clamped.
+            for call in 0..64 {
+                emulator.pc = 0x200 + call * 2;
+                emulator.op_2nnn(0x400).unwrap();
+            }
+            assert_eq!(emulator.read(STACK_START + 63 * 2 + 1), Ok(0x7E));
+            for call in (0..64).rev() {
+                emulator.op_00ee().unwrap();
+                assert_eq!(emulator.pc, 0x200 + call * 2);
+            }
+        }
     }
 
     #[test]
@@ -649,6 +954,19 @@ mod tests {
         assert_eq!(emulator.registers[0xF], 0);
     }
 
+    #[test]
+    fn test_op_8xy6_with_shift_quirk() {
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.quirks = Platform::Chip48.quirks();
+        emulator.registers[2] = 0b0000_1001;
+        emulator.registers[3] = 0b0000_0010;
+
+        emulator.op_8xy6(2, 3);
+
+        assert_eq!(emulator.registers[2], 0b0000_0100);
+        assert_eq!(emulator.registers[0xF], 1);
+    }
+
     #[test]
     fn test_op_8xy6_with_overflow() {
         let mut emulator = Chip8::init(Cursor::new(vec![]));
@@ -747,11 +1065,22 @@ mod tests {
     }
 
     #[test]
-    fn test_op_dxyn() {
-        use std::sync::{Arc, Mutex};
+    fn test_op_bnnn_with_jump_quirk() {
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.quirks = Platform::Superchip.quirks();
+        emulator.registers[0x0] = 0x10;
+        emulator.registers[0x2] = 0x20;
 
-        let display_buffer = Arc::new(Mutex::new([0xFFFFFF; window::WIDTH * window::HEIGHT]));
+        emulator.op_bnnn(2, 0x230);
+        assert_eq!(emulator.pc, 0x250);
+    }
+
+    #[test]
+    fn test_op_dxyn() {
+        let mut display_buffer = [0x0; WIDTH * HEIGHT];
         let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.lit = [true; WIDTH * HEIGHT];
+        emulator.quirks.vblank = false;
 
         emulator.registers[0] = 10; // Set vx (x position)
         emulator.registers[1] = 5; // Set vy (y position)
@@ -762,36 +1091,237 @@ mod tests {
 
         let num_of_rows = 1;
 
-        emulator.op_dxyn(0, 1, num_of_rows, &display_buffer);
+        emulator.op_dxyn(0, 1, num_of_rows).unwrap();
+        emulator.render(&mut display_buffer);
+
+        let mut expected_result = [0xFFFFFF; WIDTH * HEIGHT];
+        expected_result[5 * WIDTH + 10] = 0x0;
+        expected_result[5 * WIDTH + 11] = 0x0;
+        expected_result[5 * WIDTH + 12] = 0x0;
+        expected_result[5 * WIDTH + 13] = 0x0;
+
+        assert_eq!(display_buffer, expected_result);
+        assert_eq!(emulator.registers[0xF], 0x1);
+    }
 
-        let mut expected_result = [0xFFFFFF; window::WIDTH * window::HEIGHT];
-        expected_result[5 * window::WIDTH + 10] = 0x0;
-        expected_result[5 * window::WIDTH + 11] = 0x0;
-        expected_result[5 * window::WIDTH + 12] = 0x0;
-        expected_result[5 * window::WIDTH + 13] = 0x0;
+    // draws a full 8x2 sprite at the given position, returning the lit pixels.
+    fn draw_block(x: u8, y: u8, wrap: bool) -> Vec<(usize, usize)> {
+        let mut display_buffer = [0x0; WIDTH * HEIGHT];
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.quirks = Quirks {
+            wrap,
+            vblank: false,
+            ..Quirks::default()
+        };
+        emulator.memory[..2].copy_from_slice(&[0xFF, 0xFF]);
+        emulator.registers[..2].copy_from_slice(&[x, y]);
 
-        let buffer = display_buffer.lock().unwrap();
+        emulator.op_dxyn(0, 1, 2).unwrap();
+        emulator.render(&mut display_buffer);
 
-        assert_eq!(*buffer, expected_result);
+        (0..WIDTH * HEIGHT)
+            .filter(|pixel| display_buffer[*pixel] == 0xFFFFFF)
+            .map(|pixel| (pixel % WIDTH, pixel / WIDTH))
+            .collect()
+    }
+
+    #[test]
+    fn test_op_dxyn_at_the_edges() {
+        // inside the screen both modes draw the whole sprite.
+        assert_eq!(draw_block(56, 30, false).len(), 16);
+        assert_eq!(draw_block(56, 30, true).len(), 16);
+
+        // right edge.
+        assert_eq!(draw_block(60, 0, false).len(), 8);
+        let wrapped = draw_block(60, 0, true);
+        assert_eq!(wrapped.len(), 16);
+        assert!(wrapped.contains(&(63, 1)) && wrapped.contains(&(3, 1)));
+        assert!(!wrapped.contains(&(4, 0)));
+
+        // bottom edge.
+        assert_eq!(draw_block(0, 31, false).len(), 8);
+        let wrapped = draw_block(0, 31, true);
+        assert_eq!(wrapped.len(), 16);
+        assert!(wrapped.contains(&(7, 31)) && wrapped.contains(&(7, 0)));
+
+        // bottom right corner, three quarters of the sprite come back in the other corners.
+        assert_eq!(draw_block(63, 31, false), vec![(63, 31)]);
+        let wrapped = draw_block(63, 31, true);
+        assert_eq!(wrapped.len(), 16);
+        for pixel in [(63, 31), (0, 31), (63, 0), (6, 0)] {
+            assert!(wrapped.contains(&pixel), "{:?}", pixel);
+        }
+
+        // starting coordinates wrap in both modes.
+        assert_eq!(draw_block(64 + 2, 32 + 3, false), draw_block(2, 3, false));
+    }
+
+    #[test]
+    fn test_op_dxyn_with_vblank_quirk() {
+        let mut display_buffer = [0x0; WIDTH * HEIGHT];
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        // the default profile is the VIP's, so unknown ROMs wait as well.
+        assert_eq!(emulator.quirks, Platform::OriginalChip8.quirks());
+        assert!(emulator.quirks.vblank);
+        emulator.memory[0] = 0x80;
+
+        for _ in 0..2 {
+            emulator.pc = 0x202;
+            emulator.op_dxyn(0, 1, 1).unwrap();
+            assert_eq!(emulator.pc, 0x200);
+        }
+        emulator.render(&mut display_buffer);
+        assert_eq!(display_buffer[0], 0x0);
+
+        emulator.tick_timers();
+        emulator.pc = 0x202;
+        emulator.op_dxyn(0, 1, 1).unwrap();
+        assert_eq!(emulator.pc, 0x202);
+        emulator.render(&mut display_buffer);
+        assert_eq!(display_buffer[0], 0xFFFFFF);
+    }
+
+    #[test]
+    fn test_op_dxyn_with_palette() {
+        let mut display_buffer = [0x0; WIDTH * HEIGHT];
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.palette = Palette {
+            background: 0x112233,
+            foreground: 0xFFAA00,
+        };
+        emulator.quirks.vblank = false;
+        emulator.memory[0] = 0x80;
+
+        emulator.op_00e0();
+        emulator.op_dxyn(0, 1, 1).unwrap();
+        emulator.render(&mut display_buffer);
+        assert_eq!(display_buffer[0], 0xFFAA00);
+        assert_eq!(emulator.registers[0xF], 0x0);
+
+        // a background colour equal to the foreground does not make pixels count as lit.
+        emulator.palette.background = 0xFFAA00;
+        emulator.op_dxyn(0, 1, 1).unwrap();
         assert_eq!(emulator.registers[0xF], 0x1);
+        emulator.op_dxyn(0, 1, 1).unwrap();
+        assert_eq!(emulator.registers[0xF], 0x0);
+        emulator.render(&mut display_buffer);
+        assert_eq!(display_buffer[0], 0xFFAA00);
     }
 
     #[test]
-    fn test_op_ex9e() {
-        use std::sync::{Arc, Mutex};
+    fn test_colour_board() {
+        let mut display_buffer = [0x0; WIDTH * HEIGHT];
+        let mut emulator = Chip8::init_at(Cursor::new(vec![0x02, 0xA0]), 0x300);
+        assert_eq!((emulator.pc, emulator.memory[0x301]), (0x300, 0xA0));
+        emulator.colour_board = Some(ColourBoard::init());
+        emulator.quirks.vblank = false;
+        emulator.memory[0] = 0x80;
+        emulator.op_00e0();
+        emulator.render(&mut display_buffer);
+        assert_eq!(display_buffer[0], 0x000080);
+
+        // the second zone of the first 4 rows turns green.
+        emulator.registers[0] = 0x01;
+        emulator.registers[1] = 0x00;
+        emulator.registers[2] = 4;
+        emulator.op_bxyn(0, 2, 0);
+        // rows 2 to 4 of the third column turn yellow.
+        emulator.registers[5] = 16;
+        emulator.registers[6] = 2;
+        emulator.registers[7] = 5;
+        emulator.op_bxyn(5, 7, 3);
+
+        for (x, y, colour) in [
+            (0, 0, 0xFF0000),
+            (8, 3, 0x00FF00),
+            (8, 4, 0xFF0000),
+            (16, 4, 0xFFFF00),
+            (16, 5, 0xFF0000),
+        ] {
+            emulator.registers[0xA] = x;
+            emulator.registers[0xB] = y;
+            emulator.op_dxyn(0xA, 0xB, 1).unwrap();
+            emulator.render(&mut display_buffer);
+            assert_eq!(display_buffer[y as usize * WIDTH + x as usize], colour);
+        }
+
+        emulator.op_02a0();
+        emulator.render(&mut display_buffer);
+        assert_eq!(display_buffer[1], 0x000000);
+        assert_eq!(display_buffer[0], 0xFF0000);
+    }
+
+    #[test]
+    fn test_colour_board_clips_spans() {
+        let mut display_buffer = [0x0; WIDTH * HEIGHT];
+        let mut emulator = Chip8::init_at(Cursor::new(vec![]), 0x300);
+        emulator.colour_board = Some(ColourBoard::init());
+        emulator.lit = [true; WIDTH * HEIGHT];
+
+        // 16 columns from the seventh and 64 rows from the 25th, far past the edges.
+        emulator.registers[0] = 0xF6;
+        emulator.registers[1] = 0xF6;
+        emulator.registers[2] = 4;
+        emulator.op_bxyn(0, 2, 0);
+        // and a bxyn running off the bottom.
+        emulator.registers[5] = 0;
+        emulator.registers[6] = 30;
+        emulator.registers[7] = 5;
+        emulator.op_bxyn(5, 7, 8);
+        emulator.render(&mut display_buffer);
+
+        for (x, y, colour) in [
+            (48, 24, 0x00FF00),
+            (63, 31, 0x00FF00),
+            (0, 31, 0xFFFF00),
+            // nothing wraps around to the opposite edges.
+            (0, 24, 0xFF0000),
+            (48, 0, 0xFF0000),
+            (0, 0, 0xFF0000),
+            (0, 1, 0xFF0000),
+        ] {
+            assert_eq!(display_buffer[y * WIDTH + x], colour, "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn test_op_5xy1() {
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.registers[0] = 0x8F;
+        emulator.registers[1] = 0x31;
+
+        emulator.op_5xy1(0, 1);
+        assert_eq!(emulator.registers[0], 0xB0);
+    }
+
+    #[test]
+    fn test_second_keypad() {
+        let second_keypad = keypad(1 << 3);
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.registers[0] = 3;
+
+        emulator.op_exf2(0, &second_keypad);
+        assert_eq!(emulator.pc, 0x202);
+        emulator.op_exf5(0, &second_keypad);
+        assert_eq!(emulator.pc, 0x202);
+        emulator.op_exf5(0, &Keypad::default());
+        assert_eq!(emulator.pc, 0x204);
+    }
 
-        let key_map = Arc::new(Mutex::new(0xF0u16)); // Example key map: 11110000
+    #[test]
+    fn test_op_ex9e() {
+        let keypad = keypad(0xF0); // Example key map: 11110000
         let mut emulator = Chip8::init(Cursor::new(vec![]));
 
         emulator.registers[0] = 0;
-        emulator.op_ex9e(0, &key_map);
+        emulator.op_ex9e(0, &keypad);
 
         // Assert that the program counter is not incremented since key 0 is not pressed
         // 0x200 is the program start location
         assert_eq!(emulator.pc, 0x200);
 
         emulator.registers[0] = 4;
-        emulator.op_ex9e(0, &key_map);
+        emulator.op_ex9e(0, &keypad);
 
         // Assert that the program counter is incremented because key 4 is pressed
         assert_eq!(emulator.pc, 0x202);
@@ -799,20 +1329,18 @@ mod tests {
 
     #[test]
     fn test_op_exa1() {
-        use std::sync::{Arc, Mutex};
-
-        let key_map = Arc::new(Mutex::new(0xF0u16)); // Example key map: 11110000
+        let keypad = keypad(0xF0); // Example key map: 11110000
         let mut emulator = Chip8::init(Cursor::new(vec![]));
 
         emulator.registers[0] = 4;
-        emulator.op_exa1(0, &key_map);
+        emulator.op_exa1(0, &keypad);
 
         // Assert that the program counter is incremented since key 0 is not pressed
         // 0x200 is the program start location
         assert_eq!(emulator.pc, 0x200);
 
         emulator.registers[0] = 0;
-        emulator.op_exa1(0, &key_map);
+        emulator.op_exa1(0, &keypad);
 
         // Assert that the program counter is not incremented because key 4 is pressed
         assert_eq!(emulator.pc, 0x202);
@@ -820,24 +1348,53 @@ mod tests {
 
     #[test]
     fn test_op_fx0a() {
-        use std::sync::{Arc, Mutex};
-
-        // Initialize the key_map (0x10 means key 4 is pressed  0001 0000)
-        let key_map = Arc::new(Mutex::new(0x10u16));
+        let mut keypad = keypad(0x00);
         let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.pc = 0x202;
 
-        emulator.op_fx0a(0, &key_map);
+        emulator.op_fx0a(0, &keypad);
+        assert_eq!(emulator.pc, 0x200);
 
+        // 0x10 means key 4 is pressed (0001 0000), it counts once released.
+        set_keys(&mut keypad, 0x10);
+        emulator.pc = 0x202;
+        emulator.op_fx0a(0, &keypad);
+        assert_eq!(emulator.pc, 0x200);
+        assert!(emulator.sound_timer.get() > 0);
+
+        set_keys(&mut keypad, 0x00);
+        emulator.pc = 0x202;
+        emulator.op_fx0a(0, &keypad);
+        assert_eq!(emulator.pc, 0x202);
         assert_eq!(emulator.registers[0], 4);
     }
 
+    #[test]
+    fn test_op_fx0a_ignores_held_key() {
+        // key 4 is still held from an earlier prompt.
+        let mut keypad = keypad(0x10);
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.quirks = Platform::Superchip.quirks();
+
+        emulator.pc = 0x202;
+        emulator.op_fx0a(0, &keypad);
+        assert_eq!(emulator.pc, 0x200);
+
+        // key 2 is pressed while 4 is held, without the release quirk it counts right away.
+        set_keys(&mut keypad, 0x14);
+        emulator.pc = 0x202;
+        emulator.op_fx0a(0, &keypad);
+        assert_eq!(emulator.pc, 0x202);
+        assert_eq!(emulator.registers[0], 2);
+        assert_eq!(emulator.sound_timer.get(), 0);
+    }
+
     #[test]
     fn test_op_fx0a_no_key_press() {
-        use std::sync::{Arc, Mutex};
-        let key_map = Arc::new(Mutex::new(0x00u16)); // empty keymap
+        let keypad = keypad(0x00); // empty keymap
         let mut emulator = Chip8::init(Cursor::new(vec![]));
 
-        emulator.op_fx0a(2, &key_map);
+        emulator.op_fx0a(2, &keypad);
 
         // Assert that the program counter has decreased by 2 (indicating the instruction was skipped)
         assert_eq!(emulator.pc, 0x1FE); // initial 0x200 - 0x2 = 0x1FE
@@ -883,7 +1440,7 @@ mod tests {
         let mut emulator = Chip8::init(Cursor::new(vec![]));
         emulator.registers[0] = 234;
 
-        emulator.op_fx33(0);
+        emulator.op_fx33(0).unwrap();
 
         assert_eq!(emulator.memory[emulator.index_register as usize], 2);
         assert_eq!(emulator.memory[emulator.index_register as usize + 1], 3);
@@ -902,7 +1459,7 @@ mod tests {
 
         emulator.index_register = 0x200;
 
-        emulator.op_fx55(3);
+        emulator.op_fx55(3).unwrap();
 
         assert_eq!(emulator.memory[0x200], 0x10);
         assert_eq!(emulator.memory[0x201], 0x20);
@@ -913,6 +1470,60 @@ mod tests {
         assert_eq!(emulator.index_register, 0x204);
     }
 
+    #[test]
+    fn test_op_fx55_with_memory_quirks() {
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.quirks = Platform::Chip48.quirks();
+        emulator.index_register = 0x200;
+
+        emulator.op_fx55(3).unwrap();
+        assert_eq!(emulator.index_register, 0x203);
+
+        emulator.quirks = Platform::Superchip.quirks();
+        emulator.op_fx55(3).unwrap();
+        assert_eq!(emulator.index_register, 0x203);
+    }
+
+    #[test]
+    fn test_op_fx55_past_end_of_memory() {
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.registers[..3].copy_from_slice(&[0x10, 0x20, 0x30]);
+        emulator.index_register = 0xFFE;
+
+        emulator.op_fx55(2).unwrap();
+        assert_eq!(emulator.memory[0xFFE..], [0x10, 0x20]);
+        assert_eq!(emulator.memory[0x000], 0x30);
+
+        emulator.memory_access = MemoryAccess::Clamp;
+        emulator.index_register = 0xFFE;
+        emulator.op_fx55(2).unwrap();
+        assert_eq!(emulator.memory[0xFFE..], [0x10, 0x30]);
+
+        emulator.memory_access = MemoryAccess::Fault;
+        emulator.index_register = 0xFFE;
+        assert_eq!(
+            emulator.op_fx55(2),
+            Err(Fault::MemoryOutOfBounds { address: 0x1000 })
+        );
+    }
+
+    #[test]
+    fn test_fetch_at_end_of_memory() {
+        let mut emulator = Chip8::init(Cursor::new(vec![]));
+        emulator.memory[0xFFF] = 0x12;
+        emulator.memory[0x000] = 0x34;
+
+        emulator.pc = 0xFFF;
+        assert_eq!(emulator.fetch(), Ok(0x1234));
+
+        emulator.memory_access = MemoryAccess::Fault;
+        emulator.pc = 0xFFF;
+        assert_eq!(
+            emulator.fetch(),
+            Err(Fault::MemoryOutOfBounds { address: 0x1000 })
+        );
+    }
+
     #[test]
     fn test_op_fx65() {
         let mut emulator = Chip8::init(Cursor::new(vec![]));
@@ -924,7 +1535,7 @@ mod tests {
         emulator.memory[0x303] = 0xDD;
         emulator.memory[0x304] = 0xEE; // should not be loaded
 
-        emulator.op_fx65(3);
+        emulator.op_fx65(3).unwrap();
 
         assert_eq!(emulator.registers[0], 0xAA);
         assert_eq!(emulator.registers[1], 0xBB);
@@ -0,0 +1,84 @@
+use std::ops::Range;
+
+use crate::emulator::{DisplayBuffer, HEIGHT, WIDTH};
+
+// The VP-590 colour board CHIP-8X drives. Colours are attributes of zones 8 pixels wide and a row
+// high, lit pixels show the colour of their zone and the rest the background colour.
+const BACKGROUNDS: [u32; 4] = [0x000080, 0x000000, 0x008000, 0x800000];
+const FOREGROUNDS: [u32; 8] = [
+    0x000000, 0xFF0000, 0x0000FF, 0xFF00FF, 0x00FF00, 0xFFFF00, 0x00FFFF, 0xFFFFFF,
+];
+pub const ZONE_WIDTH: usize = 8;
+const ZONE_COLUMNS: usize = WIDTH / ZONE_WIDTH;
+// programs start out drawing red on blue.
+const DEFAULT_COLOUR: u8 = 1;
+
+#[derive(Clone, Debug)]
+pub struct ColourBoard {
+    background: usize,
+    zones: [u8; ZONE_COLUMNS * HEIGHT],
+}
+
+impl ColourBoard {
+    pub fn init() -> Self {
+        Self {
+            background: 0,
+            zones: [DEFAULT_COLOUR; ZONE_COLUMNS * HEIGHT],
+        }
+    }
+
+    // blue, black, green, red and round again.
+    pub fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUNDS.len();
+    }
+
+    // colours the zones in the given columns and pixel rows, both stop at the screen edges.
+    pub fn set_colour(&mut self, columns: Range<usize>, rows: Range<usize>, colour: u8) {
+        let columns = columns.start.min(ZONE_COLUMNS)..columns.end.min(ZONE_COLUMNS);
+        for row in rows.start.min(HEIGHT)..rows.end.min(HEIGHT) {
+            for column in columns.clone() {
+                self.zones[row * ZONE_COLUMNS + column] = colour & 0x7;
+            }
+        }
+    }
+
+    // what a pixel shows, the colour board only colours the display, what is lit is up to dxyn.
+    pub fn render(&self, lit: &[bool; WIDTH * HEIGHT], display_buffer: &mut DisplayBuffer) {
+        for (pixel, colour) in display_buffer.iter_mut().enumerate() {
+            *colour = if lit[pixel] {
+                let zone = pixel / WIDTH * ZONE_COLUMNS + pixel % WIDTH / ZONE_WIDTH;
+                FOREGROUNDS[self.zones[zone] as usize]
+            } else {
+                BACKGROUNDS[self.background]
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zones() {
+        let mut board = ColourBoard::init();
+        let mut lit = [false; WIDTH * HEIGHT];
+        let mut display_buffer = [0; WIDTH * HEIGHT];
+        board.render(&lit, &mut display_buffer);
+        assert_eq!(display_buffer[0], BACKGROUNDS[0]);
+
+        // both pixels are red, then only the second zone turns green.
+        lit[0] = true;
+        lit[ZONE_WIDTH] = true;
+        board.set_colour(1..2, 0..1, 4);
+        board.render(&lit, &mut display_buffer);
+        assert_eq!(display_buffer[0], FOREGROUNDS[1]);
+        assert_eq!(display_buffer[ZONE_WIDTH], FOREGROUNDS[4]);
+
+        board.cycle_background();
+        lit[0] = false;
+        board.render(&lit, &mut display_buffer);
+        assert_eq!(display_buffer[1], BACKGROUNDS[1]);
+        assert_eq!(display_buffer[0], BACKGROUNDS[1]);
+    }
+}
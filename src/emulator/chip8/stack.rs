@@ -0,0 +1,53 @@
+use super::Fault;
+
+// Return addresses of the subroutine calls in progress, holding at most depth of them.
+#[derive(Clone, Debug, Hash)]
+pub struct Stack {
+    entries: Vec<u16>,
+    depth: usize,
+}
+
+impl Stack {
+    pub fn init(depth: usize) -> Self {
+        Self {
+            entries: Vec::with_capacity(depth),
+            depth,
+        }
+    }
+
+    pub fn push(&mut self, address: u16) -> Result<(), Fault> {
+        if self.entries.len() >= self.depth {
+            return Err(Fault::StackOverflow);
+        }
+
+        self.entries.push(address);
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Result<u16, Fault> {
+        self.entries.pop().ok_or(Fault::StackUnderflow)
+    }
+
+    pub fn entries(&self) -> &[u16] {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_depth() {
+        let mut stack = Stack::init(2);
+
+        assert_eq!(stack.push(0x200), Ok(()));
+        assert_eq!(stack.push(0x300), Ok(()));
+        assert_eq!(stack.push(0x400), Err(Fault::StackOverflow));
+        assert_eq!(stack.entries(), [0x200, 0x300]);
+
+        assert_eq!(stack.pop(), Ok(0x300));
+        assert_eq!(stack.pop(), Ok(0x200));
+        assert_eq!(stack.pop(), Err(Fault::StackUnderflow));
+    }
+}
@@ -1,37 +1,20 @@
-use std::{
-    sync::{Arc, Mutex},
-    thread::{self, sleep},
-    time,
-};
-
-const TIMER_RATE: u64 = 60;
-
-pub struct Timer(Arc<Mutex<u8>>);
+// Counts down once per emulated frame, the emulator calls tick at 60 Hz of emulated time.
+pub struct Timer(u8);
 
 impl Timer {
-    pub fn set(&self, value: u8) {
-        let mut timer = self.0.lock().unwrap();
-        *timer = value;
-        drop(timer);
-
-        let mutex = Arc::clone(&self.0);
-        thread::spawn(move || loop {
-            let mut timer = mutex.lock().unwrap();
-            if *timer == 0 {
-                break;
-            }
-            *timer -= 1;
-            drop(timer);
-            sleep(time::Duration::from_nanos(1_000_000_000 / TIMER_RATE));
-        });
+    pub fn set(&mut self, value: u8) {
+        self.0 = value;
     }
 
     pub fn get(&self) -> u8 {
-        let timer = self.0.lock().unwrap();
-        *timer
+        self.0
+    }
+
+    pub fn tick(&mut self) {
+        self.0 = self.0.saturating_sub(1);
     }
 
     pub fn init() -> Self {
-        Timer(Arc::new(Mutex::new(0)))
+        Timer(0)
     }
 }
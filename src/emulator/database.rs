@@ -0,0 +1,127 @@
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use super::palette::{parse_color, Palette};
+use super::quirks::Platform;
+use crate::console;
+
+// Programs known by the SHA-1 of their ROM, in the format of the community CHIP-8 database
+// (https://github.com/chip-8/chip-8-database).
+const PROGRAMS: &str = include_str!("../../database/programs.json");
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    tickrate: Option<u64>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+    colors: Option<Colors>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, HashMap<String, bool>>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    // background first, then the colour of lit pixels.
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+// What the database recommends for a ROM.
+#[derive(Debug, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    // the first of the listed platforms this emulator supports.
+    pub platform: Option<Platform>,
+    // quirks that differ from the platform's usual behaviour, by database name.
+    pub quirks: HashMap<String, bool>,
+    // instructions per emulated frame.
+    pub tickrate: Option<u64>,
+    // keypad keys for the host controls, by database name such as "up" or "a".
+    pub keys: HashMap<String, u8>,
+    pub palette: Option<Palette>,
+}
+
+// the database is parsed on the first lookup and kept, a broken one counts as empty.
+fn programs() -> &'static [Program] {
+    static PARSED: OnceLock<Vec<Program>> = OnceLock::new();
+    PARSED.get_or_init(|| {
+        serde_json::from_str(PROGRAMS).unwrap_or_else(|e| {
+            console::report(format!("Error: Rom database could not be read. ({})", e));
+            Vec::new()
+        })
+    })
+}
+
+pub fn lookup(program: &[u8]) -> Option<RomInfo> {
+    let hash = format!("{:x}", Sha1::digest(program));
+
+    programs().iter().find_map(|program| {
+        let rom = program.roms.get(&hash)?;
+        let platform = rom
+            .platforms
+            .iter()
+            .find_map(|name| Platform::from_name(name));
+        let quirks = rom
+            .platforms
+            .iter()
+            .find(|name| Platform::from_name(name).is_some())
+            .and_then(|name| rom.quirky_platforms.get(name).cloned())
+            .unwrap_or_default();
+        let palette = rom
+            .colors
+            .as_ref()
+            .and_then(|colors| match colors.pixels.as_slice() {
+                [background, foreground, ..] => Some(Palette {
+                    background: parse_color(background).ok()?,
+                    foreground: parse_color(foreground).ok()?,
+                }),
+                _ => None,
+            });
+
+        Some(RomInfo {
+            title: program.title.clone(),
+            authors: program.authors.clone(),
+            platform,
+            quirks,
+            tickrate: rom.tickrate,
+            keys: rom.keys.clone(),
+            palette,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let program = std::fs::read("roms/Pong.ch8").unwrap();
+
+        let info = lookup(&program).unwrap();
+
+        assert_eq!(info.title, "Pong");
+        assert_eq!(info.platform, Some(Platform::OriginalChip8));
+        assert_eq!(info.keys.get("up"), Some(&0x1));
+        assert_eq!(info.keys.get("down"), Some(&0x4));
+    }
+
+    #[test]
+    fn test_lookup_unknown_rom() {
+        assert_eq!(lookup(&[0x12, 0x00]), None);
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use super::Frame;
+
+// Completed frames handed from the emulator to the frontend. The emulator draws into a buffer of
+// its own and publishes a copy at every frame boundary, so half drawn sprites never show.
+pub struct FrameBuffer {
+    published: Mutex<Published>,
+    ready: Condvar,
+}
+
+struct Published {
+    frame: Frame,
+    // counts up with every publish, 0 before the first one.
+    number: u64,
+}
+
+impl FrameBuffer {
+    pub fn init() -> Self {
+        Self {
+            published: Mutex::new(Published {
+                frame: Frame::blank(),
+                number: 0,
+            }),
+            ready: Condvar::new(),
+        }
+    }
+
+    pub fn publish(&self, frame: &Frame) {
+        let mut published = self.published.lock().unwrap();
+        published.frame = *frame;
+        published.number += 1;
+        self.ready.notify_all();
+    }
+
+    // the last published frame.
+    pub fn latest(&self) -> Frame {
+        self.published.lock().unwrap().frame
+    }
+
+    // waits for a frame newer than the given number, returning it with its number.
+    // Gives None when none came within the timeout.
+    pub fn wait_newer(&self, number: u64, timeout: Duration) -> Option<(Frame, u64)> {
+        let published = self.published.lock().unwrap();
+        let (published, _) = self
+            .ready
+            .wait_timeout_while(published, timeout, |published| published.number <= number)
+            .unwrap();
+
+        (published.number > number).then_some((published.frame, published.number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::{HEIGHT, WIDTH};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_wait_newer() {
+        let frames = Arc::new(FrameBuffer::init());
+        assert_eq!(frames.wait_newer(0, Duration::ZERO), None);
+
+        let lit = Frame {
+            pixels: [0xFFFFFF; WIDTH * HEIGHT],
+            lit: [true; WIDTH * HEIGHT],
+        };
+        let publisher = thread::spawn({
+            let frames = frames.clone();
+            move || frames.publish(&lit)
+        });
+        let (frame, number) = frames.wait_newer(0, Duration::from_secs(5)).unwrap();
+        publisher.join().unwrap();
+
+        assert_eq!((frame, number), (lit, 1));
+        assert_eq!(frames.wait_newer(1, Duration::ZERO), None);
+        assert_eq!(frames.latest(), frame);
+    }
+}
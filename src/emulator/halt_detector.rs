@@ -0,0 +1,134 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::Hasher;
+
+use super::machine::Machine;
+
+// Notices programs that ended in a loop nothing gets them out of, like the jump to itself test
+// ROMs finish with. A loop is idle when the machine is in exactly the same state every time it
+// jumps back and it did not read the keypad in between. Timers only count down between frames,
+// so nothing counts as idle while one of them runs.
+pub struct HaltDetector {
+    // a hash of the machine at each backward jump.
+    states: HashMap<usize, u64>,
+    // a halt is only reported once, until the program gets going again.
+    halted: bool,
+}
+
+impl HaltDetector {
+    pub fn init() -> Self {
+        Self {
+            states: HashMap::new(),
+            halted: false,
+        }
+    }
+
+    // called after every instruction with the address it was fetched from, true when it halts.
+    pub fn observe(&mut self, address: usize, instruction: u16, machine: &dyn Machine) -> bool {
+        if machine.timers_running()
+            || matches!(
+                instruction & 0xF0FF,
+                0xE09E | 0xE0A1 | 0xE0F2 | 0xE0F5 | 0xF00A
+            )
+        {
+            self.states.clear();
+            self.halted = false;
+            return false;
+        }
+
+        let jumps_back = instruction >> 12 == 0x1 && (instruction & 0xFFF) as usize <= address;
+        if !jumps_back {
+            return false;
+        }
+
+        let state = hash(machine);
+        let repeated = self.states.insert(address, state) == Some(state);
+        let halts = repeated && !self.halted;
+        self.halted = repeated;
+        halts
+    }
+}
+
+fn hash(machine: &dyn Machine) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    machine.hash_state(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::chip8::Chip8;
+    use std::io::Cursor;
+
+    // runs the loop at 0x200 the given number of times, returning whether it was seen halting.
+    fn run(program: &[u8], iterations: usize, setup: impl Fn(&mut Chip8)) -> bool {
+        let mut chip = Chip8::init(Cursor::new(program.to_vec()));
+        setup(&mut chip);
+        let mut detector = HaltDetector::init();
+
+        (0..iterations).any(|_| {
+            let address = chip.pc;
+            let instruction = chip.fetch().unwrap();
+            match instruction >> 12 {
+                0x1 => chip.op_1nnn(instruction & 0xFFF),
+                0x7 => chip.op_7xnn(0, instruction as u8),
+                _ => {}
+            }
+            chip.tick_timers();
+            detector.observe(address, instruction, &chip)
+        })
+    }
+
+    #[test]
+    fn test_jump_to_itself() {
+        assert!(run(&[0x12, 0x00], 2, |_| {}));
+    }
+
+    #[test]
+    fn test_halt_is_reported_once() {
+        let chip = Chip8::init(Cursor::new(vec![0x12, 0x00]));
+        let mut detector = HaltDetector::init();
+
+        let halts: Vec<_> = (0..4)
+            .map(|_| detector.observe(0x200, 0x1200, &chip))
+            .collect();
+        assert_eq!(halts, vec![false, true, false, false]);
+    }
+
+    #[test]
+    fn test_counting_loop_is_not_halted() {
+        // v0 += 1, jump back. It wraps around after 256 iterations.
+        assert!(!run(&[0x70, 0x01, 0x12, 0x00], 200, |_| {}));
+    }
+
+    #[test]
+    fn test_running_timer_is_not_halted() {
+        assert!(!run(&[0x12, 0x00], 5, |chip| chip.delay_timer.set(10)));
+        assert!(run(&[0x12, 0x00], 12, |chip| chip.delay_timer.set(10)));
+    }
+
+    #[test]
+    fn test_timer_polling_is_not_halted() {
+        // the timer ticks once a frame, far less often than the loop jumps back.
+        let mut chip = Chip8::init(Cursor::new(vec![]));
+        chip.delay_timer.set(1);
+        let mut detector = HaltDetector::init();
+
+        for _ in 0..3 {
+            assert!(!detector.observe(0x202, 0x1200, &chip));
+        }
+    }
+
+    #[test]
+    fn test_keypad_loop_is_not_halted() {
+        let mut chip = Chip8::init(Cursor::new(vec![]));
+        let mut detector = HaltDetector::init();
+
+        for _ in 0..3 {
+            assert!(!detector.observe(0x200, 0xE09E, &chip));
+            assert!(!detector.observe(0x202, 0x1200, &chip));
+            chip.pc = 0x200;
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// A key going down or up, stamped with when the host saw it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: u8,
+    pub pressed: bool,
+    pub time: Instant,
+}
+
+// The hex keypad as the emulated program sees it. The frontend queues events as they come, the
+// emulator latches them once per frame so a program sees the same keys for a whole frame.
+// A key tapped within a frame still reads as down for that frame.
+#[derive(Debug, Default)]
+pub struct Keypad {
+    events: VecDeque<KeyEvent>,
+    // what the frontend last reported, events are queued for the changes.
+    reported: u16,
+    // the keys that are actually down.
+    held: u16,
+    // the latched state, bit N is key N.
+    down: u16,
+    pressed: u16,
+    released: u16,
+}
+
+impl Keypad {
+    pub fn push(&mut self, event: KeyEvent) {
+        self.events.push_back(event);
+    }
+
+    // queues an event for every key that changed since the last report.
+    pub fn report(&mut self, keys: u16, time: Instant) {
+        let changed = keys ^ self.reported;
+        for key in (0..0x10).filter(|key| (changed >> key) & 0b1 == 1) {
+            self.push(KeyEvent {
+                key,
+                pressed: (keys >> key) & 0b1 == 1,
+                time,
+            });
+        }
+        self.reported = keys;
+    }
+
+    // applies the events up to the given time, starting a new frame.
+    pub fn latch(&mut self, until: Instant) {
+        self.pressed = 0;
+        self.released = 0;
+        while let Some(event) = self.events.front().filter(|event| event.time <= until) {
+            let flag = 1 << (event.key & 0xF);
+            if event.pressed {
+                self.held |= flag;
+                self.pressed |= flag;
+            } else {
+                self.held &= !flag;
+                self.released |= flag;
+            }
+            self.events.pop_front();
+        }
+        self.down = self.held | self.pressed;
+    }
+
+    pub fn is_down(&self, key: u8) -> bool {
+        (self.down >> (key & 0xF)) & 0b1 == 1
+    }
+
+    // whether the key went down during the last frame.
+    pub fn was_pressed(&self, key: u8) -> bool {
+        (self.pressed >> (key & 0xF)) & 0b1 == 1
+    }
+
+    // whether the key went up during the last frame.
+    pub fn was_released(&self, key: u8) -> bool {
+        (self.released >> (key & 0xF)) & 0b1 == 1
+    }
+
+    // the latched keys, bit N is set when key N is down.
+    pub fn keys(&self) -> u16 {
+        self.down
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_press_and_hold() {
+        let mut keypad = Keypad::default();
+        let now = Instant::now();
+
+        keypad.report(0b100, now);
+        keypad.latch(now);
+        assert!(keypad.is_down(2) && keypad.was_pressed(2));
+
+        keypad.report(0b100, now);
+        keypad.latch(now);
+        assert!(keypad.is_down(2) && !keypad.was_pressed(2));
+
+        keypad.report(0b000, now);
+        keypad.latch(now);
+        assert!(!keypad.is_down(2) && keypad.was_released(2));
+    }
+
+    #[test]
+    fn test_tap_within_a_frame() {
+        let mut keypad = Keypad::default();
+        let now = Instant::now();
+
+        keypad.report(0b10, now);
+        keypad.report(0b00, now);
+        keypad.latch(now);
+        assert!(keypad.is_down(1));
+        assert!(keypad.was_pressed(1) && keypad.was_released(1));
+
+        keypad.latch(now);
+        assert!(!keypad.is_down(1));
+    }
+
+    #[test]
+    fn test_later_events_wait_for_their_frame() {
+        let mut keypad = Keypad::default();
+        let now = Instant::now();
+        keypad.push(KeyEvent {
+            key: 5,
+            pressed: true,
+            time: now + std::time::Duration::from_secs(1),
+        });
+
+        keypad.latch(now);
+        assert!(!keypad.is_down(5));
+    }
+}
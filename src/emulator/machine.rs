@@ -0,0 +1,154 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use super::chip8::{Chip8, Fault};
+use super::keypad::Keypad;
+use super::vip::Vip;
+use super::{execute, vip_timing, Frame};
+
+// What the emulator runs programs on: the CHIP-8 virtual machine, or a COSMAC VIP running the
+// original interpreter.
+pub trait Machine: fmt::Display {
+    // the CHIP-8 program counter, for error messages.
+    fn pc(&self) -> usize;
+    fn step(
+        &mut self,
+        keypad: &Mutex<Keypad>,
+        second_keypad: &Mutex<Keypad>,
+    ) -> Result<Step, Fault>;
+    // called at every frame boundary.
+    fn end_frame(&mut self);
+    // what the display shows.
+    fn render(&self, frame: &mut Frame);
+    // whether the buzzer sounds.
+    fn sound(&self) -> bool;
+    // the halt detector's view of the machine.
+    fn timers_running(&self) -> bool;
+    fn hash_state(&self, state: &mut DefaultHasher);
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Step {
+    // the CHIP-8 instruction that ran and the address it came from, None when the machine only
+    // ran its own code.
+    pub instruction: Option<(usize, u16)>,
+    // what it took on the VIP, in machine cycles.
+    pub cycles: u64,
+}
+
+impl Machine for Chip8 {
+    fn pc(&self) -> usize {
+        self.pc
+    }
+
+    fn step(
+        &mut self,
+        keypad: &Mutex<Keypad>,
+        second_keypad: &Mutex<Keypad>,
+    ) -> Result<Step, Fault> {
+        let address = self.pc;
+        let instruction = self.fetch()?;
+        // dxyn may overwrite vx with vf, what it cost depends on the position it drew at.
+        let vx = self.registers[((instruction >> 8) & 0xF) as usize];
+        execute(self, instruction, keypad, second_keypad)?;
+        let cycles = if self.waiting_for_vblank() {
+            vip_timing::WAIT_CYCLES
+        } else {
+            vip_timing::cycles(instruction, vx, self.pc == address + 4)
+        };
+
+        Ok(Step {
+            instruction: Some((address, instruction)),
+            cycles,
+        })
+    }
+
+    fn end_frame(&mut self) {
+        self.tick_timers();
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        Chip8::render(self, &mut frame.pixels);
+        frame.lit = self.lit;
+    }
+
+    fn sound(&self) -> bool {
+        self.sound_timer.get() > 0
+    }
+
+    fn timers_running(&self) -> bool {
+        self.delay_timer.get() > 0 || self.sound_timer.get() > 0
+    }
+
+    fn hash_state(&self, state: &mut DefaultHasher) {
+        self.pc.hash(state);
+        self.index_register.hash(state);
+        self.registers.hash(state);
+        self.stack.hash(state);
+        self.memory.hash(state);
+        self.lit.hash(state);
+        self.delay_timer.get().hash(state);
+        self.sound_timer.get().hash(state);
+    }
+}
+
+impl Machine for Vip {
+    fn pc(&self) -> usize {
+        Vip::pc(self)
+    }
+
+    fn step(
+        &mut self,
+        keypad: &Mutex<Keypad>,
+        _second_keypad: &Mutex<Keypad>,
+    ) -> Result<Step, Fault> {
+        let (cycles, instruction) = Vip::step(self, &keypad.lock().unwrap());
+        Ok(Step {
+            instruction,
+            cycles,
+        })
+    }
+
+    // the CDP1861 keeps its own time.
+    fn end_frame(&mut self) {}
+
+    fn render(&self, frame: &mut Frame) {
+        Vip::render(self, frame);
+    }
+
+    fn sound(&self) -> bool {
+        Vip::sound(self)
+    }
+
+    fn timers_running(&self) -> bool {
+        Vip::timers_running(self)
+    }
+
+    fn hash_state(&self, state: &mut DefaultHasher) {
+        Vip::hash_state(self, state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::quirks::Platform;
+
+    #[test]
+    fn test_vblank_wait_cycles() {
+        let mut chip = Chip8::init([0xD0, 0x15].as_slice());
+        chip.quirks = Platform::OriginalChip8.quirks();
+        let keypad = Mutex::new(Keypad::default());
+
+        // the retries only wait, the draw is charged once the frame is over.
+        for _ in 0..3 {
+            let step = chip.step(&keypad, &keypad).unwrap();
+            assert_eq!(step.cycles, vip_timing::WAIT_CYCLES);
+        }
+        chip.end_frame();
+        let step = chip.step(&keypad, &keypad).unwrap();
+        assert_eq!(step.cycles, vip_timing::cycles(0xD015, 0, false));
+    }
+}
@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::rom_source;
+use super::{Frame, HEIGHT, WIDTH};
+
+mod font;
+
+const LINE_HEIGHT: usize = font::HEIGHT + 1;
+const CHARACTER_WIDTH: usize = font::WIDTH + 1;
+const VISIBLE_LINES: usize = HEIGHT / LINE_HEIGHT;
+// the first column holds the cursor.
+const NAME_LENGTH: usize = WIDTH / CHARACTER_WIDTH - 1;
+
+// Lists the ROMs of a directory on the display, one per line.
+pub struct Menu {
+    entries: Vec<PathBuf>,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn scan(directory: &Path) -> Self {
+        let mut entries: Vec<PathBuf> = fs::read_dir(directory)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| is_rom(path))
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.sort();
+
+        Self {
+            entries,
+            selected: 0,
+        }
+    }
+
+    pub fn up(&mut self) {
+        if !self.entries.is_empty() {
+            self.selected = (self.selected + self.entries.len() - 1) % self.entries.len();
+        }
+    }
+
+    pub fn down(&mut self) {
+        if !self.entries.is_empty() {
+            self.selected = (self.selected + 1) % self.entries.len();
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Path> {
+        self.entries.get(self.selected).map(PathBuf::as_path)
+    }
+
+    pub fn render(&self) -> Frame {
+        let mut frame = Frame::blank();
+
+        if self.entries.is_empty() {
+            draw_text(&mut frame, CHARACTER_WIDTH, 0, "NO ROMS");
+            return frame;
+        }
+
+        // scroll just enough to keep the selection on screen.
+        let first = self.selected.saturating_sub(VISIBLE_LINES - 1);
+        for (line, (index, entry)) in self
+            .entries
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(VISIBLE_LINES)
+            .enumerate()
+        {
+            let y = line * LINE_HEIGHT;
+            if index == self.selected {
+                draw_text(&mut frame, 0, y, ">");
+            }
+            draw_text(&mut frame, CHARACTER_WIDTH, y, &name(entry));
+        }
+
+        frame
+    }
+}
+
+fn is_rom(path: &Path) -> bool {
+    path.is_file() && rom_source::is_loadable(path)
+}
+
+// the file name without extension and trailing metadata like "[Author, 1979]".
+fn name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let title = stem.split(['[', '(']).next().unwrap_or_default();
+
+    title.trim().chars().take(NAME_LENGTH).collect()
+}
+
+fn draw_text(frame: &mut Frame, x: usize, y: usize, text: &str) {
+    for (position, character) in text.chars().enumerate() {
+        let left = x + position * CHARACTER_WIDTH;
+        if left + font::WIDTH > WIDTH {
+            break;
+        }
+
+        for (row, bits) in font::glyph(character).iter().enumerate() {
+            for column in 0..font::WIDTH {
+                if (bits >> (7 - column)) & 0x1 == 1 {
+                    let pixel = (y + row) * WIDTH + left + column;
+                    frame.pixels[pixel] = 0xFFFFFF;
+                    frame.lit[pixel] = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu(count: usize, selected: usize) -> Menu {
+        Menu {
+            entries: (0..count)
+                .map(|index| PathBuf::from(format!("{:X}.ch8", index)))
+                .collect(),
+            selected,
+        }
+    }
+
+    fn is_lit(frame: &Frame, x: usize, y: usize) -> bool {
+        frame.lit[y * WIDTH + x] && frame.pixels[y * WIDTH + x] == 0xFFFFFF
+    }
+
+    #[test]
+    fn test_name() {
+        let path = Path::new("roms/Breakout [Carmelo Cortez, 1979].ch8");
+
+        assert_eq!(name(path), "Breakout");
+        assert_eq!(name(Path::new("Space Intercept.ch8")), "Space Inter");
+    }
+
+    #[test]
+    fn test_navigation_wraps_around() {
+        let mut menu = menu(3, 0);
+
+        menu.up();
+        assert_eq!(menu.selected(), Some(Path::new("2.ch8")));
+
+        menu.down();
+        assert_eq!(menu.selected(), Some(Path::new("0.ch8")));
+    }
+
+    #[test]
+    fn test_render_marks_selection() {
+        let frame = menu(3, 1).render();
+
+        // the cursor's first row is a single pixel in its first column.
+        assert!(!is_lit(&frame, 0, 0));
+        assert!(is_lit(&frame, 0, LINE_HEIGHT));
+        assert!(!is_lit(&frame, 0, 2 * LINE_HEIGHT));
+    }
+
+    #[test]
+    fn test_render_scrolls_to_selection() {
+        let frame = menu(10, 7).render();
+
+        // the selection is on the last visible line.
+        assert!(is_lit(&frame, 0, (VISIBLE_LINES - 1) * LINE_HEIGHT));
+    }
+}
@@ -0,0 +1,52 @@
+use crate::emulator::chip8::fonts;
+
+pub const WIDTH: usize = 4;
+pub const HEIGHT: usize = fonts::LENGTH;
+
+// Letters and punctuation drawn in the style of the built-in hexadecimal font.
+const EXTRA_GLYPHS: [(char, [u8; HEIGHT]); 26] = [
+    ('G', [0xF0, 0x80, 0xB0, 0x90, 0xF0]),
+    ('H', [0x90, 0x90, 0xF0, 0x90, 0x90]),
+    ('I', [0xE0, 0x40, 0x40, 0x40, 0xE0]),
+    ('J', [0x70, 0x20, 0x20, 0xA0, 0xE0]),
+    ('K', [0x90, 0xA0, 0xC0, 0xA0, 0x90]),
+    ('L', [0x80, 0x80, 0x80, 0x80, 0xF0]),
+    ('M', [0x90, 0xF0, 0xF0, 0x90, 0x90]),
+    ('N', [0x90, 0xD0, 0xB0, 0x90, 0x90]),
+    ('O', [0xF0, 0x90, 0x90, 0x90, 0xF0]),
+    ('P', [0xF0, 0x90, 0xF0, 0x80, 0x80]),
+    ('Q', [0xF0, 0x90, 0x90, 0xF0, 0x10]),
+    ('R', [0xE0, 0x90, 0xE0, 0xA0, 0x90]),
+    ('S', [0xF0, 0x80, 0xF0, 0x10, 0xF0]),
+    ('T', [0xF0, 0x40, 0x40, 0x40, 0x40]),
+    ('U', [0x90, 0x90, 0x90, 0x90, 0xF0]),
+    ('V', [0x90, 0x90, 0x90, 0xA0, 0x40]),
+    ('W', [0x90, 0x90, 0xF0, 0xF0, 0x90]),
+    ('X', [0x90, 0x90, 0x60, 0x90, 0x90]),
+    ('Y', [0x90, 0x90, 0x60, 0x40, 0x40]),
+    ('Z', [0xF0, 0x10, 0x20, 0x40, 0xF0]),
+    ('-', [0x00, 0x00, 0xF0, 0x00, 0x00]),
+    ('+', [0x00, 0x40, 0xE0, 0x40, 0x00]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x40]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0xF0]),
+    ('!', [0x40, 0x40, 0x40, 0x00, 0x40]),
+    ('>', [0x80, 0x40, 0x20, 0x40, 0x80]),
+];
+
+// rows of the glyph, the pixels are the upper four bits. Unknown characters are blank.
+pub fn glyph(character: char) -> [u8; HEIGHT] {
+    let character = character.to_ascii_uppercase();
+
+    if let Some(digit) = character.to_digit(16) {
+        let start = digit as usize * fonts::LENGTH;
+        let mut rows = [0; HEIGHT];
+        rows.copy_from_slice(&fonts::FONT_SET[start..start + fonts::LENGTH]);
+        return rows;
+    }
+
+    EXTRA_GLYPHS
+        .iter()
+        .find(|(glyph, _)| *glyph == character)
+        .map(|(_, rows)| *rows)
+        .unwrap_or([0; HEIGHT])
+}
@@ -0,0 +1,38 @@
+// The colours unlit and lit pixels are drawn with, as 0RGB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub background: u32,
+    pub foreground: u32,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            background: 0x000000,
+            foreground: 0xFFFFFF,
+        }
+    }
+}
+
+// reads colours written as "#RRGGBB" or "RRGGBB".
+pub fn parse_color(color: &str) -> Result<u32, String> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' is not a colour like #RRGGBB.", color));
+    }
+
+    Ok(u32::from_str_radix(hex, 16).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#ff8000"), Ok(0xFF8000));
+        assert_eq!(parse_color("00FF00"), Ok(0x00FF00));
+        assert!(parse_color("#fff").is_err());
+        assert!(parse_color("#gggggg").is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use clap::ValueEnum;
+
+// The machines CHIP-8 programs were written for, named as in the CHIP-8 database.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Platform {
+    /// The COSMAC VIP interpreter.
+    #[default]
+    #[value(name = "chip8")]
+    OriginalChip8,
+    /// The COSMAC VIP interpreter with extra machine code routines.
+    #[value(name = "vip")]
+    HybridVip,
+    /// CHIP-8X on the COSMAC VIP with the VP-590 colour board.
+    #[value(name = "chip8x")]
+    Chip8x,
+    /// The behaviour most modern interpreters settled on.
+    #[value(name = "modern")]
+    ModernChip8,
+    /// CHIP-48 on the HP-48 calculators.
+    Chip48,
+    /// SUPER-CHIP 1.0.
+    #[value(name = "schip1")]
+    Superchip1,
+    /// SUPER-CHIP 1.1.
+    #[value(name = "schip")]
+    Superchip,
+    /// XO-CHIP.
+    #[value(name = "xochip")]
+    Xochip,
+}
+
+// What happens to memory accesses past 0xFFF, where I can point after fx1e or fx55.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum MemoryAccess {
+    /// Addresses wrap around to 0x000, like the 12 bit addresses of the COSMAC VIP.
+    #[default]
+    Wrap,
+    /// Stop the program with an error.
+    Fault,
+    /// Read and write the last byte of memory instead.
+    Clamp,
+}
+
+// The ways interpreters disagree on what an instruction does.
+// The names follow the quirks of the CHIP-8 database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    // 8xy1, 8xy2 and 8xy3 reset vf.
+    pub logic: bool,
+    // 8xy6 and 8xye shift vx in place instead of copying vy.
+    pub shift: bool,
+    // fx55 and fx65 advance I by x instead of x + 1.
+    pub memory_increment_by_x: bool,
+    // fx55 and fx65 leave I where it was.
+    pub memory_leave_i_unchanged: bool,
+    // bnnn jumps to xnn + vx instead of nnn + v0.
+    pub jump: bool,
+    // dxyn waits for the next frame before drawing, like the VIP waiting for its display interrupt.
+    pub vblank: bool,
+    // dxyn draws the parts of sprites going off the screen on the other side instead of clipping.
+    pub wrap: bool,
+    // fx0a waits for the key to be released again, not just pressed.
+    pub key_release: bool,
+    // fx0a beeps once a key is pressed, like the VIP interpreter.
+    pub key_beep: bool,
+}
+
+impl Platform {
+    // platforms are written in camel case in the database, ones this emulator lacks give None.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "originalChip8" => Some(Platform::OriginalChip8),
+            "hybridVIP" => Some(Platform::HybridVip),
+            "chip8x" => Some(Platform::Chip8x),
+            "modernChip8" => Some(Platform::ModernChip8),
+            "chip48" => Some(Platform::Chip48),
+            "superchip1" => Some(Platform::Superchip1),
+            "superchip" => Some(Platform::Superchip),
+            "xochip" => Some(Platform::Xochip),
+            _ => None,
+        }
+    }
+
+    // where programs are loaded, the CHIP-8X interpreter takes up another page.
+    pub fn program_start(self) -> usize {
+        match self {
+            Platform::Chip8x => 0x300,
+            _ => 0x200,
+        }
+    }
+
+    // bytes of memory programs can use. The VIP interpreters keep their stack and display
+    // buffer above 0xEA0.
+    pub fn memory_size(self) -> usize {
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip | Platform::Chip8x => 0xEA0,
+            _ => 0x1000,
+        }
+    }
+
+    // nested subroutine calls the interpreter has room for.
+    pub fn stack_depth(self) -> usize {
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip | Platform::Chip8x => 12,
+            Platform::Superchip1 | Platform::Superchip => 32,
+            _ => 16,
+        }
+    }
+
+    pub fn quirks(self) -> Quirks {
+        let none = Quirks {
+            logic: false,
+            shift: false,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            jump: false,
+            vblank: false,
+            wrap: false,
+            key_release: false,
+            key_beep: false,
+        };
+
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip | Platform::Chip8x => Quirks {
+                logic: true,
+                vblank: true,
+                key_release: true,
+                key_beep: true,
+                ..none
+            },
+            Platform::ModernChip8 => Quirks {
+                key_release: true,
+                ..none
+            },
+            Platform::Xochip => Quirks {
+                wrap: true,
+                key_release: true,
+                ..none
+            },
+            Platform::Chip48 => Quirks {
+                shift: true,
+                memory_increment_by_x: true,
+                jump: true,
+                ..none
+            },
+            Platform::Superchip1 | Platform::Superchip => Quirks {
+                shift: true,
+                memory_leave_i_unchanged: true,
+                jump: true,
+                ..none
+            },
+        }
+    }
+}
+
+impl Quirks {
+    // sets a quirk by its database name, unknown ones are ignored.
+    pub fn set(&mut self, name: &str, enabled: bool) {
+        match name {
+            "logic" => self.logic = enabled,
+            "shift" => self.shift = enabled,
+            "memoryIncrementByX" => self.memory_increment_by_x = enabled,
+            "memoryLeaveIUnchanged" => self.memory_leave_i_unchanged = enabled,
+            "jump" => self.jump = enabled,
+            "vblank" => self.vblank = enabled,
+            "wrap" => self.wrap = enabled,
+            _ => {}
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Platform::default().quirks()
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use super::quirks::Platform;
+
+// extensions of loadable programs, gif being Octo cartridges.
+pub const ROM_EXTENSIONS: [&str; 6] = ["ch8", "c8", "c8x", "sc8", "xo8", "gif"];
+const ARCHIVE_EXTENSION: &str = "zip";
+
+// Where a ROM is read from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RomSource {
+    File(PathBuf),
+    // given as "-" on the command line.
+    Stdin,
+    // a zip archive, the entry is needed when it holds more than one ROM.
+    Archive {
+        path: PathBuf,
+        entry: Option<String>,
+    },
+}
+
+impl RomSource {
+    pub fn init(path: PathBuf, entry: Option<String>) -> Self {
+        if path.as_os_str() == "-" {
+            RomSource::Stdin
+        } else if has_extension(&path, &[ARCHIVE_EXTENSION]) {
+            RomSource::Archive { path, entry }
+        } else {
+            RomSource::File(path)
+        }
+    }
+
+    // the file on disk, if there is one to watch.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            RomSource::File(path) | RomSource::Archive { path, .. } => Some(path),
+            RomSource::Stdin => None,
+        }
+    }
+
+    // returns the name of the ROM, which tells its format, and its contents.
+    pub fn read(&self) -> Result<(String, Vec<u8>)> {
+        let file = match self.path() {
+            Some(path) => fs::read(path)?,
+            None => {
+                let mut file = Vec::new();
+                io::stdin().read_to_end(&mut file)?;
+                file
+            }
+        };
+
+        self.open(file)
+    }
+
+    // like read, for contents of the file or stdin that were already read.
+    pub fn open(&self, file: Vec<u8>) -> Result<(String, Vec<u8>)> {
+        match self {
+            RomSource::File(path) => Ok((file_name(path), file)),
+            RomSource::Stdin => Ok(("stdin.ch8".to_string(), file)),
+            RomSource::Archive { entry, .. } => {
+                read_archive(io::Cursor::new(file), entry.as_deref())
+            }
+        }
+    }
+}
+
+// whether the menu and the command line can open the file.
+pub fn is_loadable(path: &Path) -> bool {
+    has_extension(path, &ROM_EXTENSIONS) || has_extension(path, &[ARCHIVE_EXTENSION])
+}
+
+// the platform a ROM was most likely written for, going by its extension.
+pub fn platform(name: &str) -> Platform {
+    match extension(Path::new(name)).as_deref() {
+        Some("c8x") => Platform::Chip8x,
+        Some("sc8") => Platform::Superchip,
+        Some("xo8") => Platform::Xochip,
+        _ => Platform::OriginalChip8,
+    }
+}
+
+fn read_archive(archive: impl Read + io::Seek, entry: Option<&str>) -> Result<(String, Vec<u8>)> {
+    let mut archive = zip::ZipArchive::new(archive).context("Archive could not be opened.")?;
+    let roms: Vec<String> = archive
+        .file_names()
+        .filter(|name| has_extension(Path::new(name), &ROM_EXTENSIONS))
+        .map(str::to_string)
+        .collect();
+
+    let name = match (entry, roms.as_slice()) {
+        // the entry may be given without the directories it is in.
+        (Some(entry), _) => roms
+            .iter()
+            .find(|name| *name == entry || file_name(Path::new(name)) == entry)
+            .with_context(|| format!("Archive has no ROM named '{}'.", entry))?
+            .clone(),
+        (None, [name]) => name.clone(),
+        (None, []) => bail!("Archive holds no ROM."),
+        (None, _) => bail!(
+            "Archive holds several ROMs, pick one with --entry: {}",
+            roms.join(", ")
+        ),
+    };
+
+    let mut contents = Vec::new();
+    archive.by_name(&name)?.read_to_end(&mut contents)?;
+    Ok((file_name(Path::new(&name)), contents))
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    extension(path).is_some_and(|ext| extensions.contains(&ext.as_str()))
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    fn archive(entries: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in entries {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        Cursor::new(writer.finish().unwrap().into_inner())
+    }
+
+    #[test]
+    fn test_init() {
+        assert_eq!(RomSource::init("-".into(), None), RomSource::Stdin);
+        assert_eq!(
+            RomSource::init("roms/Pong.ch8".into(), None),
+            RomSource::File("roms/Pong.ch8".into())
+        );
+        assert!(matches!(
+            RomSource::init("roms.ZIP".into(), None),
+            RomSource::Archive { .. }
+        ));
+    }
+
+    #[test]
+    fn test_platform() {
+        assert_eq!(platform("pong.ch8"), Platform::OriginalChip8);
+        assert_eq!(platform("pong.c8"), Platform::OriginalChip8);
+        assert_eq!(platform("car.SC8"), Platform::Superchip);
+        assert_eq!(platform("tetris.c8x"), Platform::Chip8x);
+        assert_eq!(platform("t8nks.xo8"), Platform::Xochip);
+    }
+
+    #[test]
+    fn test_read_archive() {
+        let single = archive(&[("readme.txt", b"hi"), ("games/pong.ch8", &[0x12, 0x00])]);
+        assert_eq!(
+            read_archive(single, None).unwrap(),
+            ("pong.ch8".to_string(), vec![0x12, 0x00])
+        );
+
+        let several = || archive(&[("pong.ch8", &[0x00]), ("car.sc8", &[0x01])]);
+        assert!(read_archive(several(), None).is_err());
+        assert_eq!(
+            read_archive(several(), Some("car.sc8")).unwrap(),
+            ("car.sc8".to_string(), vec![0x01])
+        );
+        assert!(read_archive(several(), Some("tank.ch8")).is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// Notices when the ROM file is rewritten, e.g. by an assembler in an edit-assemble-run loop.
+pub struct RomWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl RomWatcher {
+    pub fn init(path: PathBuf) -> Self {
+        let modified = modified(&path);
+        Self { path, modified }
+    }
+
+    // returns the new contents once the file has changed and can be read.
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        let modified = modified(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+
+        // assemblers may truncate the file before writing it, wait for the content.
+        let rom = fs::read(&self.path).ok().filter(|rom| !rom.is_empty())?;
+        self.modified = modified;
+        Some(rom)
+    }
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::Duration;
+
+    #[test]
+    fn test_poll() {
+        let path = std::env::temp_dir().join("rusty-chip-8-test-watcher.ch8");
+        fs::write(&path, [0x12, 0x00]).unwrap();
+        let mut watcher = RomWatcher::init(path.clone());
+        assert_eq!(watcher.poll(), None);
+
+        // file systems may keep coarse times, so the rewrite is dated explicitly.
+        fs::write(&path, [0x00, 0xE0]).unwrap();
+        let later = watcher.modified.unwrap() + Duration::from_secs(1);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        assert_eq!(watcher.poll(), Some(vec![0x00, 0xE0]));
+        assert_eq!(watcher.poll(), None);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,307 @@
+use clap::ValueEnum;
+use std::collections::HashSet;
+use std::fmt;
+
+use super::quirks::Platform;
+
+// Problems found in a ROM before it is run. Errors keep it from loading, the rest are warnings.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Diagnostic {
+    Empty,
+    TooLarge {
+        size: usize,
+        limit: usize,
+    },
+    // instructions are two bytes, a stray byte hints at a damaged file.
+    OddLength {
+        size: usize,
+    },
+    JumpOutside {
+        address: u16,
+        target: u16,
+    },
+    // an instruction the platform in use does not have, only the needed one.
+    WrongPlatform {
+        address: u16,
+        instruction: u16,
+        platform: Platform,
+        needed: Platform,
+    },
+    // an instruction of SUPER-CHIP or XO-CHIP, only their quirks are emulated.
+    Unsupported {
+        address: u16,
+        instruction: u16,
+        extension: Platform,
+    },
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        matches!(self, Diagnostic::Empty | Diagnostic::TooLarge { .. })
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::Empty => write!(f, "Rom is empty."),
+            Diagnostic::TooLarge { size, limit } => write!(
+                f,
+                "Rom is {} bytes, only {} fit in memory on this platform.",
+                size, limit
+            ),
+            Diagnostic::OddLength { size } => {
+                write!(f, "Rom is {} bytes, an odd length for a program.", size)
+            }
+            Diagnostic::JumpOutside { address, target } => write!(
+                f,
+                "Instruction at {:#05X} jumps to {:#05X}, outside the loaded program.",
+                address, target
+            ),
+            Diagnostic::WrongPlatform {
+                address,
+                instruction,
+                platform,
+                needed,
+            } => write!(
+                f,
+                "Instruction {:04X} at {:#05X} does not exist on --platform {}, the ROM probably needs --platform {}.",
+                instruction,
+                address,
+                name(*platform),
+                name(*needed)
+            ),
+            Diagnostic::Unsupported {
+                address,
+                instruction,
+                extension,
+            } => write!(
+                f,
+                "Instruction {:04X} at {:#05X} belongs to {}, which this emulator does not implement on any platform.",
+                instruction,
+                address,
+                match extension {
+                    Platform::Xochip => "XO-CHIP",
+                    _ => "SUPER-CHIP",
+                }
+            ),
+        }
+    }
+}
+
+// the platform as it is given on the command line.
+fn name(platform: Platform) -> String {
+    platform.to_possible_value().unwrap().get_name().to_string()
+}
+
+pub fn validate(program: &[u8], platform: Platform) -> Vec<Diagnostic> {
+    let start = platform.program_start();
+    let limit = platform.memory_size() - start;
+    let mut diagnostics = Vec::new();
+
+    if program.is_empty() {
+        diagnostics.push(Diagnostic::Empty);
+    }
+    if program.len() > limit {
+        diagnostics.push(Diagnostic::TooLarge {
+            size: program.len(),
+            limit,
+        });
+    }
+    if program.len() % 2 == 1 {
+        diagnostics.push(Diagnostic::OddLength {
+            size: program.len(),
+        });
+    }
+
+    let loaded = &program[..program.len().min(limit)];
+    let end = start + loaded.len();
+    // only the first instruction from elsewhere is reported, the rest tell nothing new.
+    let mut foreign = None;
+    for (address, instruction) in trace(loaded, platform) {
+        let target = instruction & 0xFFF;
+        let jumps = matches!(instruction >> 12, 0x1 | 0x2);
+        if jumps && !(start..end).contains(&(target as usize)) {
+            diagnostics.push(Diagnostic::JumpOutside {
+                address: address as u16,
+                target,
+            });
+        }
+
+        let address = address as u16;
+        let diagnostic = match extension_platform(instruction) {
+            Some(extension @ (Platform::Superchip | Platform::Xochip)) => {
+                Some(Diagnostic::Unsupported {
+                    address,
+                    instruction,
+                    extension,
+                })
+            }
+            Some(needed) if needed != platform => Some(Diagnostic::WrongPlatform {
+                address,
+                instruction,
+                platform,
+                needed,
+            }),
+            _ => None,
+        };
+        foreign = foreign.or(diagnostic);
+    }
+    diagnostics.extend(foreign);
+
+    diagnostics
+}
+
+// follows the program from its start, so data between the code is not taken for instructions.
+fn trace(program: &[u8], platform: Platform) -> Vec<(usize, u16)> {
+    let start = platform.program_start();
+    let end = start + program.len();
+    let mut visited = HashSet::new();
+    let mut pending = vec![start];
+    let mut instructions = Vec::new();
+
+    while let Some(address) = pending.pop() {
+        if address < start || address + 2 > end || !visited.insert(address) {
+            continue;
+        }
+
+        let offset = address - start;
+        let instruction = u16::from_be_bytes([program[offset], program[offset + 1]]);
+        instructions.push((address, instruction));
+
+        let next = address + 2;
+        let target = (instruction & 0xFFF) as usize;
+        match (instruction >> 12, instruction & 0xFF) {
+            // returns, exits and computed jumps end the path. On CHIP-8X bxyn colours the display.
+            (0x0, 0xEE | 0xFD) => {}
+            (0xB, _) if platform != Platform::Chip8x => {}
+            (0x1, _) => pending.push(target),
+            (0x2, _) => pending.extend([next, target]),
+            (0x3 | 0x4 | 0x5 | 0x9, _) | (0xE, 0x9E | 0xA1 | 0xF2 | 0xF5) => {
+                pending.extend([next, next + 2])
+            }
+            // XO-CHIP's long load carries its address in the next two bytes.
+            _ if instruction == 0xF000 => pending.push(next + 2),
+            _ => pending.push(next),
+        }
+    }
+
+    instructions.sort_unstable();
+    instructions
+}
+
+// the extension an instruction belongs to, None for plain CHIP-8.
+fn extension_platform(instruction: u16) -> Option<Platform> {
+    let low = instruction & 0xFF;
+    match instruction >> 12 {
+        0x0 if instruction & 0xFFF0 == 0x00D0 => Some(Platform::Xochip),
+        0x0 if instruction & 0xFFF0 == 0x00C0 && instruction != 0x00C0 => Some(Platform::Superchip),
+        0x0 if matches!(instruction, 0x00FB..=0x00FF) => Some(Platform::Superchip),
+        0x0 if instruction == 0x02A0 => Some(Platform::Chip8x),
+        0x5 if instruction & 0xF == 0x1 => Some(Platform::Chip8x),
+        0x5 if matches!(instruction & 0xF, 0x2 | 0x3) => Some(Platform::Xochip),
+        0xE if matches!(low, 0xF2 | 0xF5) => Some(Platform::Chip8x),
+        0xF if matches!(low, 0xF8 | 0xFB) => Some(Platform::Chip8x),
+        0xF if matches!(instruction, 0xF000 | 0xF002) => Some(Platform::Xochip),
+        0xF if low == 0x01 || low == 0x3A => Some(Platform::Xochip),
+        0xF if matches!(low, 0x30 | 0x75 | 0x85) => Some(Platform::Superchip),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_roms_are_valid() {
+        for rom in ["roms/Pong.ch8", "roms/2-ibm-logo.ch8"] {
+            let program = std::fs::read(rom).unwrap();
+            assert_eq!(
+                validate(&program, Platform::OriginalChip8),
+                vec![],
+                "{}",
+                rom
+            );
+        }
+    }
+
+    #[test]
+    fn test_size() {
+        assert_eq!(
+            validate(&[], Platform::OriginalChip8),
+            vec![Diagnostic::Empty]
+        );
+
+        let program = [0x12, 0x00].repeat(0x700);
+        let diagnostics = validate(&program, Platform::OriginalChip8);
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::TooLarge {
+                size: 0xE00,
+                limit: 0xCA0
+            }]
+        );
+        assert!(diagnostics[0].is_error());
+        assert_eq!(validate(&program, Platform::ModernChip8), vec![]);
+
+        assert_eq!(
+            validate(&[0x12, 0x00, 0xFF], Platform::OriginalChip8),
+            vec![Diagnostic::OddLength { size: 3 }]
+        );
+    }
+
+    #[test]
+    fn test_jump_outside() {
+        // jump over data that would read as a jump, then call past the end.
+        let program = [0x12, 0x04, 0x1F, 0xFF, 0x22, 0x10, 0x12, 0x06];
+
+        assert_eq!(
+            validate(&program, Platform::OriginalChip8),
+            vec![Diagnostic::JumpOutside {
+                address: 0x204,
+                target: 0x210
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unsupported() {
+        // hires, then loop.
+        let program = [0x00, 0xFF, 0x12, 0x02];
+        let unsupported = vec![Diagnostic::Unsupported {
+            address: 0x200,
+            instruction: 0x00FF,
+            extension: Platform::Superchip,
+        }];
+
+        // picking the platform does not help, the instruction is missing on all of them.
+        assert_eq!(validate(&program, Platform::OriginalChip8), unsupported);
+        assert_eq!(validate(&program, Platform::Superchip), unsupported);
+        assert_eq!(
+            unsupported[0].to_string(),
+            "Instruction 00FF at 0x200 belongs to SUPER-CHIP, which this emulator does not implement on any platform."
+        );
+    }
+
+    #[test]
+    fn test_chip8x() {
+        // cycle the background, colour a zone, then loop. Programs start at 0x300.
+        let program = [0x02, 0xA0, 0xB0, 0x10, 0x13, 0x04];
+
+        assert_eq!(validate(&program, Platform::Chip8x), vec![]);
+        assert_eq!(
+            validate(&program, Platform::OriginalChip8),
+            vec![Diagnostic::WrongPlatform {
+                address: 0x200,
+                instruction: 0x02A0,
+                platform: Platform::OriginalChip8,
+                needed: Platform::Chip8x
+            }]
+        );
+        assert_eq!(
+            validate(&program, Platform::ModernChip8)[0].to_string(),
+            "Instruction 02A0 at 0x200 does not exist on --platform modern, the ROM probably needs --platform chip8x."
+        );
+    }
+}
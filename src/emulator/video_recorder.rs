@@ -0,0 +1,358 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::{DisplayBuffer, FRAME_RATE, HEIGHT, WIDTH};
+use crate::capture::to_rgb;
+
+// Captures every emulated frame into an animated GIF or APNG, picked by the file extension.
+// Identical consecutive frames are merged into a single longer one to keep files small.
+pub struct VideoRecorder {
+    encoder: Encoder,
+    scale: usize,
+    width: u16,
+    height: u16,
+    pending: Option<(DisplayBuffer, u64)>,
+    recorded_frames: u64,
+    // gif delays are counted in centiseconds, keep track of the rounding.
+    written_centiseconds: u64,
+}
+
+// changed frames an APNG keeps at most, a minute of the display changing every frame in 30 MB.
+const APNG_FRAME_LIMIT: usize = 3600;
+
+enum Encoder {
+    Gif(gif::Encoder<BufWriter<File>>),
+    // APNG needs the number of frames up front, so they are kept until the end, up to
+    // APNG_FRAME_LIMIT. They are only scaled while being written, a scaled frame takes hundreds
+    // of kilobytes.
+    Apng(BufWriter<File>, Vec<(DisplayBuffer, u64)>),
+}
+
+impl VideoRecorder {
+    pub fn init(path: &Path, scale: usize) -> Result<Self> {
+        let (Ok(width), Ok(height)) = (u16::try_from(WIDTH * scale), u16::try_from(HEIGHT * scale))
+        else {
+            bail!("A capture scale of {} is too large for a video.", scale);
+        };
+        // the extension is checked first, so a bad one leaves no empty file behind.
+        let gif = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gif") => true,
+            Some("png" | "apng") => false,
+            _ => bail!("Video must have a '.gif', '.png' or '.apng' extension."),
+        };
+        let file = BufWriter::new(File::create(path)?);
+
+        let encoder = if gif {
+            let mut encoder = gif::Encoder::new(file, width, height, &[])?;
+            encoder.set_repeat(gif::Repeat::Infinite)?;
+            Encoder::Gif(encoder)
+        } else {
+            Encoder::Apng(file, Vec::new())
+        };
+
+        Ok(Self {
+            encoder,
+            scale,
+            width,
+            height,
+            pending: None,
+            recorded_frames: 0,
+            written_centiseconds: 0,
+        })
+    }
+
+    pub fn record_frame(&mut self, frame: &DisplayBuffer) -> Result<()> {
+        match self.pending.as_mut() {
+            Some((pending, count)) if pending == frame => *count += 1,
+            _ => {
+                // the frame waiting to be written counts too, finalize still has room for it.
+                if let Encoder::Apng(_, frames) = &self.encoder {
+                    if frames.len() + 1 >= APNG_FRAME_LIMIT {
+                        bail!(
+                            "APNG recordings keep at most {} changed frames, record a '.gif' for longer ones.",
+                            APNG_FRAME_LIMIT
+                        );
+                    }
+                }
+                self.write_pending()?;
+                self.pending = Some((*frame, 1));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn finalize(mut self) -> Result<()> {
+        self.write_pending()?;
+
+        match self.encoder {
+            Encoder::Gif(encoder) => encoder.into_inner()?.flush()?,
+            Encoder::Apng(file, mut frames) => {
+                if frames.is_empty() {
+                    frames.push(([0u32; WIDTH * HEIGHT], 1));
+                }
+
+                let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                // a delay only goes up to u16::MAX frames, a longer still screen is written
+                // several times.
+                let parts = |count: u64| count.div_ceil(u16::MAX as u64);
+                let written: u64 = frames.iter().map(|(_, count)| parts(*count)).sum();
+                encoder.set_animated(written as u32, 0)?;
+
+                let mut writer = encoder.write_header()?;
+                for (frame, count) in frames {
+                    let image = to_rgb(&frame, self.scale);
+                    let mut remaining = count;
+                    while remaining > 0 {
+                        let delay = remaining.min(u16::MAX as u64);
+                        writer.set_frame_delay(delay as u16, FRAME_RATE as u16)?;
+                        writer.write_image_data(&image)?;
+                        remaining -= delay;
+                    }
+                }
+                writer.finish()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_pending(&mut self) -> Result<()> {
+        let Some((frame, count)) = self.pending.take() else {
+            return Ok(());
+        };
+
+        self.recorded_frames += count;
+
+        match &mut self.encoder {
+            Encoder::Gif(encoder) => {
+                let end = self.recorded_frames * 100 / FRAME_RATE;
+                // the display only ever shows a handful of colours, so they make up the palette and
+                // the frame needs no quantisation.
+                let mut gif_frame = match indexed(&frame, self.scale) {
+                    Some((palette, pixels)) => gif::Frame::from_palette_pixels(
+                        self.width,
+                        self.height,
+                        pixels,
+                        palette,
+                        None,
+                    ),
+                    None => gif::Frame::from_rgb_speed(
+                        self.width,
+                        self.height,
+                        &to_rgb(&frame, self.scale),
+                        30,
+                    ),
+                };
+                // a delay only goes up to u16::MAX centiseconds, a longer still screen is shown
+                // for several frames.
+                let mut delay = end - self.written_centiseconds;
+                while delay > u16::MAX as u64 {
+                    gif_frame.delay = u16::MAX;
+                    encoder.write_frame(&gif_frame)?;
+                    delay -= u16::MAX as u64;
+                }
+                gif_frame.delay = delay as u16;
+                self.written_centiseconds = end;
+                encoder.write_frame(&gif_frame)?;
+            }
+            Encoder::Apng(_, frames) => frames.push((frame, count)),
+        }
+
+        Ok(())
+    }
+}
+
+// the frame's colours as an RGB palette and its scaled pixels as indices into it, None when there
+// are too many colours for a GIF palette.
+fn indexed(frame: &DisplayBuffer, scale: usize) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut indices = HashMap::new();
+    let mut palette = Vec::new();
+    let mut row_indices = Vec::with_capacity(WIDTH * scale);
+    let mut pixels = Vec::with_capacity(WIDTH * HEIGHT * scale * scale);
+
+    for row in frame.chunks(WIDTH) {
+        row_indices.clear();
+        for &pixel in row {
+            let index = match indices.get(&pixel) {
+                Some(&index) => index,
+                None => {
+                    let index = u8::try_from(indices.len()).ok()?;
+                    let [_, r, g, b] = pixel.to_be_bytes();
+                    palette.extend_from_slice(&[r, g, b]);
+                    indices.insert(pixel, index);
+                    index
+                }
+            };
+            row_indices.extend(std::iter::repeat_n(index, scale));
+        }
+        for _ in 0..scale {
+            pixels.extend_from_slice(&row_indices);
+        }
+    }
+
+    Some((palette, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_frames_are_merged() {
+        let path = std::env::temp_dir().join("rusty-chip-8-test-identical-frames.gif");
+        let mut lit = [0u32; WIDTH * HEIGHT];
+        lit[0] = 0xFFFFFF;
+
+        let mut recorder = VideoRecorder::init(&path, 1).unwrap();
+        for _ in 0..30 {
+            recorder.record_frame(&lit).unwrap();
+        }
+        recorder.record_frame(&[0u32; WIDTH * HEIGHT]).unwrap();
+        recorder.finalize().unwrap();
+
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(File::open(&path).unwrap())
+            .unwrap();
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        // half a second, then a single 1/60 of a second frame.
+        assert_eq!(delays, vec![50, 1]);
+    }
+
+    #[test]
+    fn test_long_still_frames_are_split() {
+        let path = std::env::temp_dir().join("rusty-chip-8-test-long-still-frames.gif");
+
+        let mut recorder = VideoRecorder::init(&path, 1).unwrap();
+        // 700 seconds of the same screen, longer than a single gif delay.
+        for _ in 0..700 * FRAME_RATE {
+            recorder.record_frame(&[0u32; WIDTH * HEIGHT]).unwrap();
+        }
+        recorder
+            .record_frame(&[0xFFFFFFu32; WIDTH * HEIGHT])
+            .unwrap();
+        recorder.finalize().unwrap();
+
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(File::open(&path).unwrap())
+            .unwrap();
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(delays, vec![u16::MAX, 4465, 1]);
+    }
+
+    #[test]
+    fn test_long_still_apng_frames_are_split() {
+        let path = std::env::temp_dir().join("rusty-chip-8-test-long-still-apng-frames.png");
+
+        let mut recorder = VideoRecorder::init(&path, 1).unwrap();
+        // 70000 frames, about 19 minutes, of the same screen.
+        for _ in 0..70000 {
+            recorder.record_frame(&[0u32; WIDTH * HEIGHT]).unwrap();
+        }
+        recorder
+            .record_frame(&[0xFFFFFFu32; WIDTH * HEIGHT])
+            .unwrap();
+        recorder.finalize().unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let mut delays = Vec::new();
+        while reader.next_frame(&mut buffer).is_ok() {
+            delays.push(reader.info().frame_control.unwrap().delay_num);
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(delays, vec![u16::MAX, 4465, 1]);
+    }
+
+    #[test]
+    fn test_apng_frame_limit() {
+        let path = std::env::temp_dir().join("rusty-chip-8-test-apng-frame-limit.png");
+        let mut lit = [0u32; WIDTH * HEIGHT];
+        lit[0] = 0xFFFFFF;
+
+        let mut recorder = VideoRecorder::init(&path, 1).unwrap();
+        let mut recorded = 0;
+        while recorded < APNG_FRAME_LIMIT * 2 {
+            let frame = if recorded % 2 == 0 {
+                lit
+            } else {
+                [0; WIDTH * HEIGHT]
+            };
+            if recorder.record_frame(&frame).is_err() {
+                break;
+            }
+            recorded += 1;
+        }
+        recorder.finalize().unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let reader = decoder.read_info().unwrap();
+        let frames = reader.info().animation_control.unwrap().num_frames;
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(recorded, APNG_FRAME_LIMIT);
+        assert_eq!(frames, APNG_FRAME_LIMIT as u32);
+    }
+
+    #[test]
+    fn test_apng() {
+        let path = std::env::temp_dir().join("rusty-chip-8-test-apng.png");
+        let mut lit = [0u32; WIDTH * HEIGHT];
+        lit[0] = 0xFFFFFF;
+
+        let mut recorder = VideoRecorder::init(&path, 2).unwrap();
+        for frame in [lit, lit, [0u32; WIDTH * HEIGHT]] {
+            recorder.record_frame(&frame).unwrap();
+        }
+        recorder.finalize().unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut first = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut first).unwrap();
+        let info = reader.info();
+        let (width, frames) = (info.width, info.animation_control.unwrap().num_frames);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(width, WIDTH as u32 * 2);
+        assert_eq!(frames, 2);
+        assert_eq!(first[..6], [0xFF; 6]);
+        assert_eq!(first[6..9], [0x00; 3]);
+    }
+
+    #[test]
+    fn test_indexed() {
+        let mut frame = [0x000080u32; WIDTH * HEIGHT];
+        frame[1] = 0xFF0000;
+
+        let (palette, pixels) = indexed(&frame, 2).unwrap();
+        assert_eq!(palette, vec![0x00, 0x00, 0x80, 0xFF, 0x00, 0x00]);
+        assert_eq!(pixels.len(), WIDTH * HEIGHT * 4);
+        assert_eq!(pixels[..4], [0, 0, 1, 1]);
+        assert_eq!(pixels[WIDTH * 2..WIDTH * 2 + 4], [0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn test_scale_too_large() {
+        let path = std::env::temp_dir().join("rusty-chip-8-test-scale-too-large.gif");
+        assert!(VideoRecorder::init(&path, 2048).is_err());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,409 @@
+use anyhow::{bail, Result};
+use cdp1802::{Bus, Cdp1802};
+use cdp1861::Cdp1861;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use super::keypad::Keypad;
+use super::palette::Palette;
+use super::{Frame, HEIGHT, WIDTH};
+
+mod cdp1802;
+mod cdp1861;
+
+// The COSMAC VIP itself: a CDP1802 running a CHIP-8 interpreter out of its 4K of RAM, the CDP1861
+// showing the display page and the hex keypad. The interpreter is an image of the original, so
+// programs run with its timing and all of its quirks.
+pub const FRAME_CYCLES: u64 = cdp1861::FRAME_CYCLES;
+const RAM_SIZE: usize = 0x1000;
+// the monitor ROM answers at 0x8000 and above.
+const ROM_SIZE: usize = 0x200;
+const ROM_START: u16 = 0x8000;
+// the CHIP-8X interpreter takes up three pages, the original two.
+const INTERPRETER_SIZE: usize = 0x300;
+// the most cycles run per step when the interpreter fetches no instruction, a sixteenth of a frame
+// keeps the sleeps between steps around a millisecond.
+const STEP_CYCLES: u64 = FRAME_CYCLES / 16;
+// LDA R5, the interpreter keeps the CHIP-8 program counter in R5 and reads instructions through it.
+const FETCH: u8 = 0x45;
+// the interrupt routine saves T and D below the stack pointer R2, at whatever point it comes.
+const STACK_SCRATCH: usize = 0x10;
+
+// Without a monitor image its display interrupt routine is stood in for, the interpreter sets R1
+// to 0x8146 and relies on it. It points R0 at the display page from RB.1, repeats every row on 4
+// lines until EF1 marks the end of the display, then counts down the timers in R8.1 and R8.0 and
+// clears Q once the sound timer runs out.
+const INTERRUPT_EXIT: usize = 0x144;
+const INTERRUPT_ROUTINE: [u8; 46] = [
+    // exit: restore D, then X and P.
+    0x72, 0x70, //
+    // entry: save T and D, R9 counts interrupts for the random numbers.
+    0x22, 0x78, 0x22, 0x52, 0x19, 0xF8, 0x00, 0xA0, 0x9B, 0xB0, 0xE2, 0xE2, //
+    // 4 lines of a row, each takes 6 cycles and a DMA.
+    0x80, 0xE2, 0xE2, 0x20, 0xA0, 0xE2, 0x20, 0xA0, 0xE2, 0x20, 0xA0, 0x3C, 0x52, //
+    // the timers are counted down with DEC, which leaves DF alone.
+    0x98, 0x32, 0x66, 0xAB, 0x2B, 0x8B, 0xB8, //
+    0x88, 0x32, 0x6F, 0xAB, 0x2B, 0x8B, 0xA8, 0x3A, 0x70, 0x7A, 0x30, 0x44,
+];
+
+// What is loaded into the machine besides the program, both are user supplied.
+#[derive(Clone, Debug)]
+pub struct VipImages {
+    // the CHIP-8 interpreter, loaded at 0x000.
+    interpreter: Vec<u8>,
+    // the monitor ROM, for interpreters that call more of it than the interrupt routine.
+    monitor: Option<Vec<u8>>,
+}
+
+impl VipImages {
+    pub fn load(interpreter: &Path, monitor: Option<&Path>) -> Result<Self> {
+        let interpreter = std::fs::read(interpreter)?;
+        if interpreter.len() > INTERPRETER_SIZE {
+            bail!(
+                "The interpreter image is {} bytes, it has to fit below {:#05X}.",
+                interpreter.len(),
+                INTERPRETER_SIZE
+            );
+        }
+
+        let monitor = monitor.map(std::fs::read).transpose()?;
+        if let Some(monitor) = monitor.as_ref().filter(|monitor| monitor.len() > ROM_SIZE) {
+            bail!(
+                "The monitor image is {} bytes, the ROM holds {}.",
+                monitor.len(),
+                ROM_SIZE
+            );
+        }
+
+        Ok(Self {
+            interpreter,
+            monitor,
+        })
+    }
+}
+
+pub struct Vip {
+    cpu: Cdp1802,
+    board: Board,
+    palette: Palette,
+    // what the CDP1861 has shown so far.
+    lit: [bool; WIDTH * HEIGHT],
+    // the address of the instruction whose first byte the interpreter has read.
+    fetching: Option<u16>,
+}
+
+// everything on the bus besides the CPU.
+struct Board {
+    ram: [u8; RAM_SIZE],
+    rom: [u8; ROM_SIZE],
+    // after a reset the monitor also answers at 0, until the CPU jumps up to 0x8000.
+    rom_shadow: bool,
+    video: Cdp1861,
+    // the key OUT 2 selected, EF3 tells whether it is down.
+    key: u8,
+    keys: u16,
+}
+
+impl Vip {
+    // the program goes where the platform's interpreter expects it.
+    pub fn init(images: &VipImages, program: &[u8], start: usize, palette: Palette) -> Self {
+        let mut ram = [0; RAM_SIZE];
+        ram[..images.interpreter.len()].copy_from_slice(&images.interpreter);
+        let length = program.len().min(RAM_SIZE - start);
+        ram[start..start + length].copy_from_slice(&program[..length]);
+
+        let mut cpu = Cdp1802::init();
+        let mut rom = [0; ROM_SIZE];
+        match &images.monitor {
+            Some(monitor) => rom[..monitor.len()].copy_from_slice(monitor),
+            None => {
+                let routine = INTERRUPT_EXIT..INTERRUPT_EXIT + INTERRUPT_ROUTINE.len();
+                rom[routine].copy_from_slice(&INTERRUPT_ROUTINE);
+                // the monitor hands over with the top page of RAM in R1.1.
+                cpu.r[1] = ((RAM_SIZE >> 8) as u16 - 1) << 8;
+            }
+        }
+
+        Self {
+            cpu,
+            board: Board {
+                ram,
+                rom,
+                rom_shadow: images.monitor.is_some(),
+                video: Cdp1861::init(),
+                key: 0,
+                keys: 0,
+            },
+            palette,
+            lit: [false; WIDTH * HEIGHT],
+            fetching: None,
+        }
+    }
+
+    // the CHIP-8 program counter.
+    pub fn pc(&self) -> usize {
+        self.cpu.r[5] as usize
+    }
+
+    // runs the machine until the interpreter has fetched a CHIP-8 instruction, or for a slice of a
+    // frame at most. Returns the cycles that passed, and the instruction with its address.
+    pub fn step(&mut self, keypad: &Keypad) -> (u64, Option<(usize, u16)>) {
+        self.board.keys = keypad.keys();
+        let mut cycles = 0;
+        while cycles < STEP_CYCLES {
+            let (taken, fetched) = self.tick();
+            cycles += taken;
+            if fetched.is_some() {
+                return (cycles, fetched);
+            }
+        }
+        (cycles, None)
+    }
+
+    pub fn render(&self, frame: &mut Frame) {
+        for (colour, &lit) in frame.pixels.iter_mut().zip(self.lit.iter()) {
+            *colour = if lit {
+                self.palette.foreground
+            } else {
+                self.palette.background
+            };
+        }
+        frame.lit = self.lit;
+    }
+
+    // the buzzer sounds while Q is set.
+    pub fn sound(&self) -> bool {
+        self.cpu.q
+    }
+
+    // the interpreter counts the timers down in R8.1 and R8.0.
+    pub fn timers_running(&self) -> bool {
+        self.cpu.r[8] != 0
+    }
+
+    // the state the program can see. Display interrupts move R0 and R9 and leave T and D below the
+    // stack, none of which the program notices.
+    pub fn hash_state(&self, state: &mut impl Hasher) {
+        let cpu = &self.cpu;
+        for (index, register) in cpu.r.iter().enumerate() {
+            if index != 0 && index != 9 {
+                register.hash(state);
+            }
+        }
+        (cpu.p, cpu.x, cpu.d, cpu.df, cpu.q).hash(state);
+        let stack = cpu.r[2] as usize % RAM_SIZE;
+        self.board.ram[..stack.saturating_sub(STACK_SCRATCH)].hash(state);
+        self.board.ram[stack..].hash(state);
+    }
+
+    // an instruction is fetched once the interpreter reads its second byte, or moves on to the
+    // next one without reading it through R5.
+    fn fetch(&mut self) -> Option<(usize, u16)> {
+        let pc = self.cpu.pc() as usize;
+        if self.cpu.idle || pc >= RAM_SIZE || self.board.rom_shadow || self.board.ram[pc] != FETCH {
+            return None;
+        }
+
+        let address = self.cpu.r[5];
+        let first = match self.fetching.replace(address) {
+            Some(first) if first.wrapping_add(1) == address => {
+                self.fetching = None;
+                first
+            }
+            first => first?,
+        } as usize;
+        let bytes = [first, first + 1].map(|address| self.board.ram[address % RAM_SIZE]);
+        Some((first, u16::from_be_bytes(bytes)))
+    }
+
+    // the interrupt and DMA come before the next instruction when they are due.
+    fn tick(&mut self) -> (u64, Option<(usize, u16)>) {
+        let mut fetched = None;
+        let cycles = if self.board.video.interrupt_due() && self.cpu.interrupt() {
+            self.board.video.acknowledge_interrupt();
+            1
+        } else if let Some(row) = self.board.video.dma_due() {
+            for column in 0..cdp1861::LINE_BYTES as usize {
+                let byte = self.cpu.dma_out(&mut self.board);
+                for bit in 0..8 {
+                    self.lit[row * WIDTH + column * 8 + bit] = (byte >> (7 - bit)) & 0b1 == 1;
+                }
+            }
+            self.board.video.finish_dma();
+            cdp1861::LINE_BYTES
+        } else {
+            fetched = self.fetch();
+            self.cpu.execute(&mut self.board)
+        };
+
+        self.board.video.advance(cycles);
+        (cycles, fetched)
+    }
+}
+
+impl Bus for Board {
+    fn read(&mut self, address: u16) -> u8 {
+        if address >= ROM_START {
+            self.rom_shadow = false;
+        }
+        if address >= ROM_START || self.rom_shadow {
+            self.rom[address as usize % ROM_SIZE]
+        } else {
+            self.ram[address as usize % RAM_SIZE]
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if address < ROM_START {
+            self.ram[address as usize % RAM_SIZE] = value;
+        }
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.video.turn_off(),
+            2 => self.key = value & 0xF,
+            _ => {}
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.video.turn_on();
+        }
+        0
+    }
+
+    fn flag(&mut self, line: u8) -> bool {
+        match line {
+            1 => self.video.ef1(),
+            3 => (self.keys >> self.key) & 0b1 == 1,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Vip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cpu = &self.cpu;
+        writeln!(
+            f,
+            "P: {:X}, X: {:X}, D: {:#04X}, DF: {}, Q: {}, IE: {}",
+            cpu.p, cpu.x, cpu.d, cpu.df as u8, cpu.q as u8, cpu.ie as u8
+        )?;
+        writeln!(f, "Registers: {:04X?}", cpu.r)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::halt_detector::HaltDetector;
+    use std::time::Instant;
+
+    const PROGRAM_START: usize = 0x200;
+
+    // a stand in interpreter: display page in RB.1, R2 as stack, interrupt routine in R1, then it
+    // turns the display on and runs the given code in R3.
+    fn images(code: &[u8]) -> VipImages {
+        let mut interpreter = vec![
+            0x91, 0xBB, 0xFF, 0x01, 0xB2, 0xF8, 0xCF, 0xA2, 0xF8, 0x81, 0xB1, 0xF8, 0x46, 0xA1,
+            0xF8, 0x00, 0xB3, 0xF8, 0x20, 0xA3, 0xD3,
+        ];
+        interpreter.resize(0x20, 0);
+        interpreter.extend([0xE2, 0x69]);
+        interpreter.extend(code);
+        VipImages {
+            interpreter,
+            monitor: None,
+        }
+    }
+
+    fn run_frames(vip: &mut Vip, frames: u64) {
+        let keypad = Keypad::default();
+        let mut cycles = 0;
+        while cycles < frames * FRAME_CYCLES {
+            cycles += vip.step(&keypad).0;
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        // fills the first byte of the display page and waits.
+        let code = [
+            0xF8, 0x0F, 0xB4, 0xF8, 0x00, 0xA4, 0xF8, 0xA5, 0x54, 0x30, 0x2B,
+        ];
+        let mut vip = Vip::init(&images(&code), &[], PROGRAM_START, Palette::default());
+        run_frames(&mut vip, 2);
+
+        let palette = Palette::default();
+        let mut frame = Frame::blank();
+        vip.render(&mut frame);
+        assert_eq!(
+            frame.lit[..8],
+            [true, false, true, false, false, true, false, true]
+        );
+        assert_eq!(frame.pixels[0], palette.foreground);
+        assert_eq!(frame.pixels[WIDTH], palette.background);
+    }
+
+    #[test]
+    fn test_timers() {
+        // delay timer 3, sound timer 2 with Q set, then waits.
+        let code = [0xF8, 0x03, 0xB8, 0xF8, 0x02, 0xA8, 0x7B, 0x30, 0x29];
+        let mut vip = Vip::init(&images(&code), &[], PROGRAM_START, Palette::default());
+        run_frames(&mut vip, 1);
+        assert!(vip.sound());
+
+        run_frames(&mut vip, 3);
+        assert_eq!(vip.cpu.r[8], 0x0000);
+        assert!(!vip.sound());
+    }
+
+    #[test]
+    fn test_keypad() {
+        // OUT 2 selects key 5, D is set to 1 while it is down.
+        let code = [
+            0xF8, 0x05, 0x52, 0x62, 0x22, 0x36, 0x2D, 0xF8, 0x00, 0x30, 0x2B, 0xF8, 0x01, 0x30,
+            0x2F,
+        ];
+        let mut vip = Vip::init(&images(&code), &[], PROGRAM_START, Palette::default());
+        let mut keypad = Keypad::default();
+        keypad.report(1 << 5, Instant::now());
+        keypad.latch(Instant::now());
+        vip.step(&keypad);
+
+        assert_eq!(vip.cpu.d, 0x01);
+    }
+
+    // an interpreter that only knows 1nnn: R5 starts at 0x200, the first byte gives the high
+    // nibble of the target and the second its low byte.
+    const JUMPS: [u8; 15] = [
+        0xF8, 0x02, 0xB5, 0xF8, 0x00, 0xA5, 0x45, 0xFA, 0x0F, 0xBF, 0x45, 0xA5, 0x9F, 0xB5, 0x30,
+    ];
+
+    #[test]
+    fn test_fetch() {
+        let mut code = JUMPS.to_vec();
+        code.push(0x28);
+        // jump to 0x204, then jump to itself.
+        let program = [0x12, 0x04, 0x00, 0x00, 0x12, 0x04];
+        let mut vip = Vip::init(&images(&code), &program, PROGRAM_START, Palette::default());
+        let keypad = Keypad::default();
+
+        assert_eq!(vip.step(&keypad).1, Some((0x200, 0x1204)));
+        assert_eq!(vip.step(&keypad).1, Some((0x204, 0x1204)));
+
+        // the loop looks the same every time it comes round, whatever the interrupts did.
+        let mut detector = HaltDetector::init();
+        let halted = (0..2000).any(|_| {
+            let (_, fetched) = vip.step(&keypad);
+            let (address, instruction) = fetched.unwrap();
+            detector.observe(address, instruction, &vip)
+        });
+        assert!(halted);
+    }
+}
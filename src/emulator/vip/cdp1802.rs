@@ -0,0 +1,434 @@
+// What the CPU is wired to: memory, the I/O ports and the EF flag inputs.
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    // OUT 1 to 7, with the byte read from memory.
+    fn output(&mut self, port: u8, value: u8);
+    // INP 1 to 7, returns the byte put on the data bus.
+    fn input(&mut self, port: u8) -> u8;
+    // EF1 to EF4.
+    fn flag(&mut self, line: u8) -> bool;
+}
+
+// The RCA CDP1802. Sixteen 16 bit registers, any of which can be the program counter (P) or the
+// data pointer (X). Time is counted in machine cycles of 8 clock cycles.
+#[derive(Clone, Debug, Default)]
+pub struct Cdp1802 {
+    pub r: [u16; 0x10],
+    pub p: u8,
+    pub x: u8,
+    pub d: u8,
+    pub df: bool,
+    // X and P saved by an interrupt.
+    pub t: u8,
+    // interrupts enabled.
+    pub ie: bool,
+    // the Q output, which drives the VIP's buzzer.
+    pub q: bool,
+    // stopped by IDL until an interrupt or DMA.
+    pub idle: bool,
+}
+
+impl Cdp1802 {
+    // the state after a reset, running from address 0 with R0 as program counter.
+    pub fn init() -> Self {
+        Self {
+            ie: true,
+            ..Self::default()
+        }
+    }
+
+    // where the next instruction is fetched from.
+    pub fn pc(&self) -> u16 {
+        self.r[self.p as usize]
+    }
+
+    // runs one instruction, returning the machine cycles it took.
+    pub fn execute(&mut self, bus: &mut impl Bus) -> u64 {
+        if self.idle {
+            return 1;
+        }
+
+        let opcode = self.fetch(bus);
+        let n = (opcode & 0xF) as usize;
+        let x = self.x as usize;
+        match opcode >> 4 {
+            0x0 if n == 0 => self.idle = true,
+            0x0 => self.d = bus.read(self.r[n]),
+            0x1 => self.r[n] = self.r[n].wrapping_add(1),
+            0x2 => self.r[n] = self.r[n].wrapping_sub(1),
+            0x3 => {
+                let taken = self.condition(n as u8, bus);
+                self.short_branch(bus, taken);
+            }
+            0x4 => {
+                self.d = bus.read(self.r[n]);
+                self.r[n] = self.r[n].wrapping_add(1);
+            }
+            0x5 => bus.write(self.r[n], self.d),
+            0x6 => match n {
+                0x0 => self.r[x] = self.r[x].wrapping_add(1),
+                0x1..=0x7 => {
+                    let value = bus.read(self.r[x]);
+                    bus.output(n as u8, value);
+                    self.r[x] = self.r[x].wrapping_add(1);
+                }
+                // 68 does nothing on the 1802.
+                0x8 => {}
+                _ => {
+                    let value = bus.input(n as u8 - 8);
+                    bus.write(self.r[x], value);
+                    self.d = value;
+                }
+            },
+            0x7 => self.execute_control(n as u8, bus),
+            0x8 => self.d = self.r[n] as u8,
+            0x9 => self.d = (self.r[n] >> 8) as u8,
+            0xA => self.r[n] = self.r[n] & 0xFF00 | self.d as u16,
+            0xB => self.r[n] = self.r[n] & 0x00FF | (self.d as u16) << 8,
+            0xC => {
+                self.long_branch(n as u8, bus);
+                return 3;
+            }
+            0xD => self.p = n as u8,
+            0xE => self.x = n as u8,
+            _ => self.execute_alu(n as u8, bus),
+        }
+        2
+    }
+
+    // takes an interrupt if they are enabled, returning whether it did. Costs one machine cycle.
+    pub fn interrupt(&mut self) -> bool {
+        if !self.ie {
+            return false;
+        }
+        self.t = self.x << 4 | self.p;
+        self.p = 1;
+        self.x = 2;
+        self.ie = false;
+        self.idle = false;
+        true
+    }
+
+    // a DMA out cycle, the byte at R0 goes to the device reading it.
+    pub fn dma_out(&mut self, bus: &mut impl Bus) -> u8 {
+        let value = bus.read(self.r[0]);
+        self.r[0] = self.r[0].wrapping_add(1);
+        self.idle = false;
+        value
+    }
+
+    fn fetch(&mut self, bus: &mut impl Bus) -> u8 {
+        let p = self.p as usize;
+        let value = bus.read(self.r[p]);
+        self.r[p] = self.r[p].wrapping_add(1);
+        value
+    }
+
+    // the conditions of the branch instructions, the upper half negates the lower one.
+    fn condition(&mut self, n: u8, bus: &mut impl Bus) -> bool {
+        let condition = match n & 0x7 {
+            0x0 => true,
+            0x1 => self.q,
+            0x2 => self.d == 0,
+            0x3 => self.df,
+            line => bus.flag(line - 3),
+        };
+        // 38 is a branch that is never taken, it skips the target byte.
+        condition != (n & 0x8 != 0)
+    }
+
+    fn short_branch(&mut self, bus: &mut impl Bus, taken: bool) {
+        let p = self.p as usize;
+        if taken {
+            let target = bus.read(self.r[p]);
+            self.r[p] = self.r[p] & 0xFF00 | target as u16;
+        } else {
+            self.r[p] = self.r[p].wrapping_add(1);
+        }
+    }
+
+    fn long_branch(&mut self, n: u8, bus: &mut impl Bus) {
+        let p = self.p as usize;
+        let condition = match n & 0x3 {
+            0x0 => true,
+            0x1 => self.q,
+            0x2 => self.d == 0,
+            _ => self.df,
+        };
+
+        // C0 to C3 and C9 to CB branch to the address in the next two bytes, the others skip them.
+        // The upper half negates the lower one, C4 does nothing and C8 always skips.
+        let (branches, taken) = match n {
+            0x0..=0x3 => (true, condition),
+            0x9..=0xB => (true, !condition),
+            0x4 => (false, false),
+            0x8 => (false, true),
+            0xC => (false, self.ie),
+            0x5..=0x7 => (false, !condition),
+            _ => (false, condition),
+        };
+
+        match (branches, taken) {
+            (true, true) => {
+                let high = bus.read(self.r[p]);
+                let low = bus.read(self.r[p].wrapping_add(1));
+                self.r[p] = u16::from_be_bytes([high, low]);
+            }
+            (false, false) => {}
+            _ => self.r[p] = self.r[p].wrapping_add(2),
+        }
+    }
+
+    fn execute_control(&mut self, n: u8, bus: &mut impl Bus) {
+        let x = self.x as usize;
+        match n {
+            0x0 | 0x1 => {
+                let value = bus.read(self.r[x]);
+                self.r[x] = self.r[x].wrapping_add(1);
+                self.x = value >> 4;
+                self.p = value & 0xF;
+                self.ie = n == 0x0;
+            }
+            0x2 => {
+                self.d = bus.read(self.r[x]);
+                self.r[x] = self.r[x].wrapping_add(1);
+            }
+            0x3 => {
+                bus.write(self.r[x], self.d);
+                self.r[x] = self.r[x].wrapping_sub(1);
+            }
+            0x4 => {
+                let value = bus.read(self.r[x]);
+                self.add(value, self.df);
+            }
+            0x5 => {
+                let value = bus.read(self.r[x]);
+                self.subtract(value, self.d, self.df);
+            }
+            0x6 => {
+                let carry = self.d & 0x1 == 1;
+                self.d = self.d >> 1 | (self.df as u8) << 7;
+                self.df = carry;
+            }
+            0x7 => {
+                let value = bus.read(self.r[x]);
+                self.subtract(self.d, value, self.df);
+            }
+            0x8 => bus.write(self.r[x], self.t),
+            0x9 => {
+                self.t = self.x << 4 | self.p;
+                bus.write(self.r[2], self.t);
+                self.x = self.p;
+                self.r[2] = self.r[2].wrapping_sub(1);
+            }
+            0xA => self.q = false,
+            0xB => self.q = true,
+            0xC => {
+                let value = self.fetch(bus);
+                self.add(value, self.df);
+            }
+            0xD => {
+                let value = self.fetch(bus);
+                self.subtract(value, self.d, self.df);
+            }
+            0xE => {
+                let carry = self.d & 0x80 != 0;
+                self.d = self.d << 1 | self.df as u8;
+                self.df = carry;
+            }
+            _ => {
+                let value = self.fetch(bus);
+                self.subtract(self.d, value, self.df);
+            }
+        }
+    }
+
+    // F0 to F7 work on M(RX), F8 to FF on the byte after the instruction.
+    fn execute_alu(&mut self, n: u8, bus: &mut impl Bus) {
+        let value = match n {
+            0x6 | 0xE => 0,
+            0x0..=0x7 => bus.read(self.r[self.x as usize]),
+            _ => self.fetch(bus),
+        };
+
+        match n & 0x7 {
+            0x0 => self.d = value,
+            0x1 => self.d |= value,
+            0x2 => self.d &= value,
+            0x3 => self.d ^= value,
+            0x4 => self.add(value, false),
+            0x5 => self.subtract(value, self.d, true),
+            0x6 if n == 0x6 => {
+                self.df = self.d & 0x1 == 1;
+                self.d >>= 1;
+            }
+            0x6 => {
+                self.df = self.d & 0x80 != 0;
+                self.d <<= 1;
+            }
+            _ => self.subtract(self.d, value, true),
+        }
+    }
+
+    fn add(&mut self, value: u8, carry: bool) {
+        let sum = self.d as u16 + value as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    // DF is set when there was no borrow, it takes part as the inverted borrow.
+    fn subtract(&mut self, minuend: u8, subtrahend: u8, no_borrow: bool) {
+        let difference = minuend as i16 - subtrahend as i16 - !no_borrow as i16;
+        self.d = difference as u8;
+        self.df = difference >= 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestBus {
+        memory: Vec<u8>,
+        output: Vec<(u8, u8)>,
+        flags: [bool; 4],
+    }
+
+    impl Bus for TestBus {
+        fn read(&mut self, address: u16) -> u8 {
+            self.memory[address as usize]
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            self.memory[address as usize] = value;
+        }
+
+        fn output(&mut self, port: u8, value: u8) {
+            self.output.push((port, value));
+        }
+
+        fn input(&mut self, port: u8) -> u8 {
+            port * 0x10
+        }
+
+        fn flag(&mut self, line: u8) -> bool {
+            self.flags[line as usize - 1]
+        }
+    }
+
+    // runs the program from 0 until it reaches IDL, returning the CPU.
+    fn run(program: &[u8], bus: &mut TestBus) -> Cdp1802 {
+        bus.memory = program.to_vec();
+        bus.memory.resize(0x100, 0);
+        let mut cpu = Cdp1802::init();
+        for _ in 0..1000 {
+            cpu.execute(bus);
+            if cpu.idle {
+                break;
+            }
+        }
+        cpu
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        // R3 = 0x0080, store 0x42 there and load it back with LDA, then GLO R3.
+        let program = [
+            0xF8, 0x00, 0xB3, 0xF8, 0x80, 0xA3, 0xF8, 0x42, 0x53, 0xF8, 0x00, 0x43, 0x83, 0x00,
+        ];
+        let mut bus = TestBus::default();
+        let cpu = run(&program, &mut bus);
+
+        assert_eq!(bus.memory[0x80], 0x42);
+        assert_eq!(cpu.r[3], 0x0081);
+        assert_eq!(cpu.d, 0x81);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut bus = TestBus::default();
+
+        // 0xF0 + 0x20 carries.
+        let cpu = run(&[0xF8, 0xF0, 0xFC, 0x20, 0x00], &mut bus);
+        assert_eq!((cpu.d, cpu.df), (0x10, true));
+
+        // 0x10 - 0x20 borrows, DF is cleared.
+        let cpu = run(&[0xF8, 0x10, 0xFF, 0x20, 0x00], &mut bus);
+        assert_eq!((cpu.d, cpu.df), (0xF0, false));
+
+        // SDI subtracts D from the immediate byte.
+        let cpu = run(&[0xF8, 0x10, 0xFD, 0x30, 0x00], &mut bus);
+        assert_eq!((cpu.d, cpu.df), (0x20, true));
+
+        // SHL moves the top bit into DF, SHRC brings it back in on the left.
+        let cpu = run(&[0xF8, 0x81, 0xFE, 0x76, 0x00], &mut bus);
+        assert_eq!((cpu.d, cpu.df), (0x81, false));
+    }
+
+    #[test]
+    fn test_branches() {
+        let mut bus = TestBus::default();
+
+        // BZ is taken with D = 0, skipping LDI 1.
+        let cpu = run(&[0xF8, 0x00, 0x32, 0x06, 0xF8, 0x01, 0x00], &mut bus);
+        assert_eq!(cpu.d, 0x00);
+
+        // BN1 falls through when EF1 is set.
+        bus.flags[0] = true;
+        let cpu = run(&[0x3C, 0x04, 0xF8, 0x01, 0x00], &mut bus);
+        assert_eq!(cpu.d, 0x01);
+
+        // LBR to 0x0010, then LSKP over LDI 1.
+        let mut program = vec![0xC0, 0x00, 0x10];
+        program.resize(0x10, 0);
+        program.extend([0xC8, 0xF8, 0x01, 0xF8, 0x02, 0x00]);
+        let cpu = run(&program, &mut bus);
+        assert_eq!(cpu.d, 0x02);
+    }
+
+    #[test]
+    fn test_call_and_return() {
+        // SEX 2, R2 = 0x80, R3 = 0x10, SEP 3. The routine at 0x10 sets Q and returns with SEP 0.
+        let mut program = vec![0xE2, 0xF8, 0x80, 0xA2, 0xF8, 0x10, 0xA3, 0xD3, 0x7A, 0x00];
+        program.resize(0x10, 0);
+        program.extend([0x7B, 0xD0]);
+        let mut bus = TestBus::default();
+        let cpu = run(&program, &mut bus);
+
+        // Q was cleared again after returning.
+        assert!(!cpu.q);
+        assert_eq!(cpu.p, 0);
+        assert_eq!(cpu.r[3], 0x12);
+    }
+
+    #[test]
+    fn test_io() {
+        // SEX 3, R3 = 0x20, OUT 2 sends M(R3), INP 1 stores the input byte at R3.
+        let mut program = vec![0xE3, 0xF8, 0x20, 0xA3, 0x62, 0x69, 0x00];
+        program.resize(0x20, 0);
+        program.extend([0x07, 0x00]);
+        let mut bus = TestBus::default();
+        let cpu = run(&program, &mut bus);
+
+        assert_eq!(bus.output, vec![(2, 0x07)]);
+        assert_eq!(cpu.d, 0x10);
+        assert_eq!(bus.memory[0x21], 0x10);
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let mut cpu = Cdp1802::init();
+        cpu.p = 3;
+        cpu.x = 5;
+        cpu.idle = true;
+
+        assert!(cpu.interrupt());
+        assert_eq!(
+            (cpu.t, cpu.p, cpu.x, cpu.ie, cpu.idle),
+            (0x53, 1, 2, false, false)
+        );
+        assert!(!cpu.interrupt());
+    }
+}
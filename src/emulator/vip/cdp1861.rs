@@ -0,0 +1,133 @@
+// The RCA CDP1861 video chip of the VIP. It counts 262 lines of 14 machine cycles per frame and
+// shows 128 of them, taking the 8 bytes of every line from memory by DMA. Each of the 32 rows of
+// the 64x32 display is made of 4 lines showing the same bytes, the interrupt routine rewinds R0
+// for them.
+pub const LINE_CYCLES: u64 = 14;
+const LINES: u64 = 262;
+pub const FRAME_CYCLES: u64 = LINE_CYCLES * LINES;
+pub const LINE_BYTES: u64 = 8;
+const DISPLAY_START: u64 = 80;
+const DISPLAY_END: u64 = DISPLAY_START + 128;
+// where in a line its DMA starts.
+const DMA_CYCLE: u64 = 6;
+// the interrupt comes early enough for the routine to set R0 before the first DMA.
+const INTERRUPT_CYCLE: u64 = DISPLAY_START * LINE_CYCLES + DMA_CYCLE - 29;
+
+#[derive(Clone, Debug, Default)]
+pub struct Cdp1861 {
+    on: bool,
+    // cycles into the current frame.
+    cycle: u64,
+    interrupted: bool,
+    // the next display line waiting for its bytes.
+    line: u64,
+}
+
+impl Cdp1861 {
+    pub fn init() -> Self {
+        Self {
+            line: DISPLAY_START,
+            ..Self::default()
+        }
+    }
+
+    // INP 1 turns the display on, lines already passed in this frame stay dark.
+    pub fn turn_on(&mut self) {
+        if !self.on {
+            self.on = true;
+            self.interrupted = self.cycle >= INTERRUPT_CYCLE;
+            self.line = (self.cycle / LINE_CYCLES + 1).max(DISPLAY_START);
+        }
+    }
+
+    // OUT 1 turns it off.
+    pub fn turn_off(&mut self) {
+        self.on = false;
+    }
+
+    pub fn advance(&mut self, cycles: u64) {
+        self.cycle += cycles;
+        if self.cycle >= FRAME_CYCLES {
+            self.cycle %= FRAME_CYCLES;
+            self.interrupted = false;
+            self.line = DISPLAY_START;
+        }
+    }
+
+    // whether the interrupt is raised, it stays up until taken or the display starts.
+    pub fn interrupt_due(&self) -> bool {
+        self.on
+            && !self.interrupted
+            && (INTERRUPT_CYCLE..DISPLAY_START * LINE_CYCLES).contains(&self.cycle)
+    }
+
+    pub fn acknowledge_interrupt(&mut self) {
+        self.interrupted = true;
+    }
+
+    // the display row whose bytes are due now, if any.
+    pub fn dma_due(&self) -> Option<usize> {
+        let due =
+            self.on && self.line < DISPLAY_END && self.cycle >= self.line * LINE_CYCLES + DMA_CYCLE;
+        due.then_some(((self.line - DISPLAY_START) / 4) as usize)
+    }
+
+    pub fn finish_dma(&mut self) {
+        self.line += 1;
+    }
+
+    // EF1 tells the program the display is about to start or end, over the last 4 lines before.
+    pub fn ef1(&self) -> bool {
+        let line = self.cycle / LINE_CYCLES;
+        self.on
+            && ((DISPLAY_START - 4..DISPLAY_START).contains(&line)
+                || (DISPLAY_END - 4..DISPLAY_END).contains(&line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame() {
+        let mut video = Cdp1861::init();
+        video.turn_on();
+
+        let mut interrupts = 0;
+        let mut rows = Vec::new();
+        for _ in 0..FRAME_CYCLES {
+            if video.interrupt_due() {
+                assert_eq!(video.cycle, INTERRUPT_CYCLE);
+                video.acknowledge_interrupt();
+                interrupts += 1;
+            }
+            if let Some(row) = video.dma_due() {
+                rows.push(row);
+                video.finish_dma();
+            }
+            video.advance(1);
+        }
+
+        assert_eq!(interrupts, 1);
+        assert_eq!(rows.len(), 128);
+        assert_eq!(rows.first(), Some(&0));
+        assert_eq!(rows.last(), Some(&31));
+        assert_eq!(video.cycle, 0);
+        assert!(!video.interrupted);
+    }
+
+    #[test]
+    fn test_off() {
+        let mut video = Cdp1861::init();
+        video.advance(INTERRUPT_CYCLE);
+        assert!(!video.interrupt_due() && !video.ef1());
+
+        video.turn_on();
+        assert!(!video.interrupt_due());
+        video.advance(DISPLAY_START * LINE_CYCLES);
+        assert!(video.dma_due().is_some());
+        video.turn_off();
+        assert!(video.dma_due().is_none());
+    }
+}
@@ -0,0 +1,74 @@
+// How long the COSMAC VIP interpreter took for each instruction, in 1802 machine cycles of
+// 8 clock cycles at 1.76 MHz. The counts come from reading the interpreter's code, they are close
+// to the real machine but not exact for every path through it.
+const CYCLES_PER_FRAME: u64 = 3668;
+// every frame the display interrupt runs and the CDP1861 takes 8 cycles on each of its 128 lines
+// to read the display buffer, the interpreter gets what is left.
+const INTERRUPT_CYCLES: u64 = 46 + 128 * 8;
+pub const FRAME_CYCLES: u64 = CYCLES_PER_FRAME - INTERRUPT_CYCLES;
+// fetching and decoding an instruction before running it.
+const FETCH_CYCLES: u64 = 40;
+// what a taken skip adds to a conditional instruction.
+const SKIP_CYCLES: u64 = 4;
+// the interpreter idling until the display interrupt, charged for every retry of a dxyn waiting
+// for the next frame. The sprite is only paid for once it is drawn.
+pub const WAIT_CYCLES: u64 = 8;
+
+// the cycles an instruction took, given vx as it was before the instruction changed it.
+pub fn cycles(instruction: u16, vx: u8, skipped: bool) -> u64 {
+    let x = ((instruction >> 8) & 0xF) as u64;
+    let rows = (instruction & 0xF) as u64;
+    let skip = if skipped { SKIP_CYCLES } else { 0 };
+
+    let execution = match (instruction >> 12, instruction & 0xFF) {
+        (0x0, 0xE0) => 24 + 3078,
+        (0x0, 0xEE) => 10,
+        (0x1, _) => 12,
+        (0x2, _) => 26,
+        (0x3 | 0x4, _) => 10 + skip,
+        (0x5 | 0x9, _) => 14 + skip,
+        (0x6, _) => 6,
+        (0x7, _) => 10,
+        (0x8, _) => 44,
+        (0xA, _) => 12,
+        (0xB, _) => 22,
+        (0xC, _) => 36,
+        // sprites not on a byte boundary are shifted into two bytes of the display buffer.
+        (0xD, _) => {
+            let aligned = vx.is_multiple_of(8);
+            26 + rows * if aligned { 46 } else { 68 }
+        }
+        (0xE, _) => 14 + skip,
+        // the digits are found by repeated subtraction.
+        (0xF, 0x33) => {
+            let value = vx as u64;
+            84 + (value / 100 + value / 10 % 10 + value % 10) * 16
+        }
+        (0xF, 0x55 | 0x65) => 14 + 14 * (x + 1),
+        (0xF, 0x1E | 0x29) => 16,
+        _ => 10,
+    };
+
+    FETCH_CYCLES + execution
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycles() {
+        assert_eq!(cycles(0x6005, 0, false), 46);
+        assert_eq!(cycles(0x3005, 0, true), cycles(0x3005, 0, false) + 4);
+
+        // a clear takes longer than the time a frame leaves the interpreter.
+        assert!(cycles(0x00E0, 0, false) > FRAME_CYCLES);
+
+        assert!(cycles(0xD015, 9, false) > cycles(0xD015, 8, false));
+        assert_eq!(
+            cycles(0xF033, 199, false),
+            cycles(0xF033, 0, false) + 19 * 16
+        );
+        assert_eq!(cycles(0xF255, 0, false), cycles(0xF055, 0, false) + 2 * 14);
+    }
+}
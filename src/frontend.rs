@@ -0,0 +1,199 @@
+use display_filter::DisplayFilter;
+use std::collections::HashMap;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use crate::emulator::{Frame, Shared};
+
+pub mod audio;
+pub mod display_filter;
+pub mod headless;
+pub mod recording;
+
+// TODO: move it to a config file
+const REFRESH_RATE: u64 = 60;
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / REFRESH_RATE);
+
+// Actions bound to hotkeys that are handled by the emulator thread.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    ToggleVideoRecording,
+    Screenshot,
+    // restart the program that was loaded.
+    Reset,
+    // read the ROM from disk again and restart it.
+    HardReset,
+    // leave the game for the ROM picker.
+    Menu,
+    MenuUp,
+    MenuDown,
+    MenuSelect,
+}
+
+// Host keys besides the keypad that a ROM can have mapped onto keypad keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Control {
+    Up,
+    Down,
+    Left,
+    Right,
+    A,
+    B,
+}
+
+impl Control {
+    // controls are named as in the keys of the CHIP-8 database.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "up" => Some(Control::Up),
+            "down" => Some(Control::Down),
+            "left" => Some(Control::Left),
+            "right" => Some(Control::Right),
+            "a" => Some(Control::A),
+            "b" => Some(Control::B),
+            _ => None,
+        }
+    }
+}
+
+// the keypad key each control presses.
+pub type Bindings = HashMap<Control, u8>;
+
+// A host for the emulator: something that can show frames, read the keypad and play the buzzer.
+pub trait Frontend {
+    fn present(&mut self, frame: &Frame);
+
+    // returns the held keys, bit N is set when key N is down.
+    fn poll_input(&mut self) -> u16;
+
+    // the held keys of the second keypad CHIP-8X programs can read.
+    fn poll_second_keypad(&mut self) -> u16 {
+        0
+    }
+
+    // held controls, only pressing keypad keys when the ROM binds them.
+    fn poll_controls(&mut self) -> Vec<Control> {
+        Vec::new()
+    }
+
+    // hotkeys pressed since the last call.
+    fn poll_commands(&mut self) -> Vec<Command> {
+        Vec::new()
+    }
+
+    fn play_tone(&mut self);
+
+    fn stop_tone(&mut self);
+
+    fn should_quit(&self) -> bool;
+}
+
+// drives the frontend at the refresh rate, exchanging state with the emulator thread.
+pub fn run(
+    frontend: &mut dyn Frontend,
+    shared: Shared,
+    commands: Sender<Command>,
+    mut filter: DisplayFilter,
+) {
+    let mut playing = false;
+    let mut frame = Frame::blank();
+    let mut frame_number = 0;
+
+    // the emulator sets quit when its run ends.
+    while !frontend.should_quit() && !*shared.quit.lock().unwrap() {
+        let mut keys = frontend.poll_input();
+        let bindings = shared.bindings.lock().unwrap();
+        for control in frontend.poll_controls() {
+            if let Some(key) = bindings.get(&control) {
+                keys |= 1 << key;
+            }
+        }
+        drop(bindings);
+        let now = Instant::now();
+        shared.keypad.lock().unwrap().report(keys, now);
+        let second_keys = frontend.poll_second_keypad();
+        shared
+            .second_keypad
+            .lock()
+            .unwrap()
+            .report(second_keys, now);
+
+        for command in frontend.poll_commands() {
+            // the emulator thread only stops after the frontend, nobody to tell otherwise.
+            let _ = commands.send(command);
+        }
+
+        let tone = *shared.tone.lock().unwrap();
+        if tone != playing {
+            if tone {
+                frontend.play_tone();
+            } else {
+                frontend.stop_tone();
+            }
+            playing = tone;
+        }
+
+        // waiting for the emulator's next frame paces the loop. Without one in time the last frame
+        // is shown again, so input is still read while the emulator is paused.
+        if let Some((next, number)) = shared.frames.wait_newer(frame_number, FRAME_DURATION) {
+            frame = next;
+            frame_number = number;
+        }
+
+        let filtered = filter.apply(&frame, frame_number);
+        frontend.present(&filtered);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use display_filter::{FilterMode, DEFAULT_STRENGTH};
+    use recording::RecordingFrontend;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_run() {
+        let mut frame = Frame::blank();
+        frame.pixels[10] = 0xFFFFFF;
+        frame.lit[10] = true;
+        let shared = Shared::init();
+        shared.frames.publish(&frame);
+        *shared.tone.lock().unwrap() = true;
+        let mut frontend = RecordingFrontend::init([0b10, 0b100], 2);
+
+        run(
+            &mut frontend,
+            shared.clone(),
+            mpsc::channel().0,
+            DisplayFilter::init(FilterMode::None, DEFAULT_STRENGTH),
+        );
+
+        assert_eq!(frontend.frames, vec![frame, frame]);
+        assert_eq!(frontend.audio.frames(), vec![true, true]);
+        let mut keypad = shared.keypad.lock().unwrap();
+        keypad.latch(Instant::now());
+        assert_eq!(keypad.keys(), 0b110);
+        keypad.latch(Instant::now());
+        assert_eq!(keypad.keys(), 0b100);
+    }
+
+    #[test]
+    fn test_run_with_bindings() {
+        let shared = Shared::init();
+        shared.bindings.lock().unwrap().insert(Control::Up, 0x1);
+        let mut frontend = RecordingFrontend::init([0b100], 1);
+        frontend.controls = vec![Control::Up, Control::Down];
+
+        run(
+            &mut frontend,
+            shared.clone(),
+            mpsc::channel().0,
+            DisplayFilter::init(FilterMode::None, DEFAULT_STRENGTH),
+        );
+
+        let mut keypad = shared.keypad.lock().unwrap();
+        keypad.latch(Instant::now());
+        assert_eq!(keypad.keys(), 0b110);
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::Result;
+use rodio::{OutputStream, Sink};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use tone::{Tone, ToneConfig, ToneControl};
+
+use crate::console;
+
+pub mod tone;
+
+// Plays the buzzer, the frontends turn it on and off following the sound timer.
+pub trait AudioBackend {
+    fn play(&mut self);
+
+    fn stop(&mut self);
+
+    fn set_muted(&mut self, _muted: bool) {}
+
+    // called by the frontend every time it presents a frame.
+    fn end_frame(&mut self) {}
+}
+
+// falls back to silence on machines without a sound device, e.g. CI containers.
+pub fn default_backend(config: ToneConfig) -> Box<dyn AudioBackend> {
+    match RodioBackend::init(config) {
+        Ok(backend) => Box::new(backend),
+        Err(e) => {
+            console::report(format!(
+                "Warning: no audio output available, running silent. ({})",
+                e
+            ));
+            Box::new(NullBackend)
+        }
+    }
+}
+
+pub struct RodioBackend {
+    control: Arc<ToneControl>,
+    _track: Sink,
+    _stream: OutputStream,
+}
+
+impl RodioBackend {
+    pub fn init(config: ToneConfig) -> Result<Self> {
+        let (_stream, stream_handle) = OutputStream::try_default()?;
+        let sink = Sink::try_new(&stream_handle)?;
+        let control = Arc::new(ToneControl::default());
+        sink.append(Tone::init(config, Arc::clone(&control)));
+
+        // stream should not be dropped while we need to play the sound.
+        Ok(Self {
+            control,
+            _track: sink,
+            _stream,
+        })
+    }
+}
+
+impl AudioBackend for RodioBackend {
+    fn play(&mut self) {
+        self.control.gate.store(true, Ordering::Relaxed);
+    }
+
+    fn stop(&mut self) {
+        self.control.gate.store(false, Ordering::Relaxed);
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.control.muted.store(muted, Ordering::Relaxed);
+    }
+}
+
+pub struct NullBackend;
+
+impl AudioBackend for NullBackend {
+    fn play(&mut self) {}
+
+    fn stop(&mut self) {}
+}
+
+// Records whether the tone was on at the end of every frame, clones share the recording.
+#[derive(Clone, Default)]
+pub struct CapturingBackend {
+    playing: bool,
+    frames: Arc<Mutex<Vec<bool>>>,
+}
+
+impl CapturingBackend {
+    pub fn frames(&self) -> Vec<bool> {
+        self.frames.lock().unwrap().clone()
+    }
+}
+
+impl AudioBackend for CapturingBackend {
+    fn play(&mut self) {
+        self.playing = true;
+    }
+
+    fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    fn end_frame(&mut self) {
+        self.frames.lock().unwrap().push(self.playing);
+    }
+}
@@ -0,0 +1,213 @@
+use clap::ValueEnum;
+use rodio::Source;
+use std::f32::consts::TAU;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const SAMPLE_RATE: u32 = 48_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Triangle,
+    Sawtooth,
+}
+
+impl Waveform {
+    // phase is the position within one period, between 0.0 and 1.0.
+    fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Waveform::Sine => (phase * TAU).sin(),
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ToneConfig {
+    pub waveform: Waveform,
+    pub frequency: f32,
+    pub volume: f32,
+    pub attack: Duration,
+    pub release: Duration,
+}
+
+impl Default for ToneConfig {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Sine,
+            frequency: 440.0,
+            volume: 0.2,
+            attack: Duration::from_millis(5),
+            release: Duration::from_millis(5),
+        }
+    }
+}
+
+// reads a pitch in Hz, the oscillator needs a positive and finite one.
+pub fn parse_frequency(frequency: &str) -> Result<f32, String> {
+    match frequency.parse::<f32>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        _ => Err(format!(
+            "'{}' is not a positive frequency in Hz.",
+            frequency
+        )),
+    }
+}
+
+// Switches shared between the frontend and the audio thread, which must never block on a lock.
+#[derive(Default)]
+pub struct ToneControl {
+    pub gate: AtomicBool,
+    pub muted: AtomicBool,
+}
+
+// An endless buzzer, the gate fades it in and out instead of cutting the wave, which would click.
+pub struct Tone {
+    config: ToneConfig,
+    control: Arc<ToneControl>,
+    phase: f32,
+    gain: f32,
+}
+
+impl Tone {
+    pub fn init(config: ToneConfig, control: Arc<ToneControl>) -> Self {
+        Self {
+            config,
+            control,
+            phase: 0.0,
+            gain: 0.0,
+        }
+    }
+
+    fn envelope_step(&self, target: f32) -> f32 {
+        let ramp = if target > self.gain {
+            self.config.attack
+        } else {
+            self.config.release
+        };
+
+        let samples = ramp.as_secs_f32() * SAMPLE_RATE as f32;
+        if samples < 1.0 {
+            1.0
+        } else {
+            1.0 / samples
+        }
+    }
+}
+
+impl Iterator for Tone {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let open = self.control.gate.load(Ordering::Relaxed)
+            && !self.control.muted.load(Ordering::Relaxed);
+        let target = if open { 1.0 } else { 0.0 };
+
+        let step = self.envelope_step(target);
+        self.gain = if target > self.gain {
+            (self.gain + step).min(target)
+        } else {
+            (self.gain - step).max(target)
+        };
+
+        let sample = self.config.waveform.sample(self.phase) * self.config.volume * self.gain;
+        self.phase = (self.phase + self.config.frequency / SAMPLE_RATE as f32).fract();
+
+        Some(sample)
+    }
+}
+
+impl Source for Tone {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waveforms() {
+        assert!(Waveform::Sine.sample(0.25) > 0.999);
+        assert_eq!(Waveform::Square.sample(0.25), 1.0);
+        assert_eq!(Waveform::Square.sample(0.75), -1.0);
+        assert_eq!(Waveform::Triangle.sample(0.0), -1.0);
+        assert_eq!(Waveform::Triangle.sample(0.5), 1.0);
+        assert_eq!(Waveform::Sawtooth.sample(0.0), -1.0);
+        assert_eq!(Waveform::Sawtooth.sample(0.75), 0.5);
+    }
+
+    #[test]
+    fn test_parse_frequency() {
+        assert_eq!(parse_frequency("440"), Ok(440.0));
+        assert_eq!(parse_frequency("27.5"), Ok(27.5));
+        for invalid in ["0", "-440", "NaN", "inf", "loud"] {
+            assert!(parse_frequency(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_envelope_ramps_instead_of_clicking() {
+        let config = ToneConfig {
+            waveform: Waveform::Square,
+            volume: 1.0,
+            attack: Duration::from_millis(1),
+            release: Duration::from_millis(1),
+            ..ToneConfig::default()
+        };
+        let control = Arc::new(ToneControl::default());
+        let mut tone = Tone::init(config, Arc::clone(&control));
+
+        assert_eq!(tone.next(), Some(0.0));
+
+        // 1ms at 48kHz is 48 samples to full volume.
+        control.gate.store(true, Ordering::Relaxed);
+        let first = tone.next().unwrap();
+        assert!(first > 0.0 && first < 0.1);
+        let peak = tone.nth(46).unwrap().abs();
+        assert!(peak > 0.99);
+
+        control.gate.store(false, Ordering::Relaxed);
+        let released = tone.next().unwrap().abs();
+        assert!(released > 0.9 && released < 1.0);
+        assert_eq!(tone.nth(47), Some(0.0));
+    }
+
+    #[test]
+    fn test_mute_silences_an_open_gate() {
+        let config = ToneConfig {
+            attack: Duration::ZERO,
+            ..ToneConfig::default()
+        };
+        let control = Arc::new(ToneControl::default());
+        control.gate.store(true, Ordering::Relaxed);
+        control.muted.store(true, Ordering::Relaxed);
+        let tone = Tone::init(config, control);
+
+        assert!(tone.take(100).all(|sample| sample == 0.0));
+    }
+}
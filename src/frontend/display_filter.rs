@@ -0,0 +1,213 @@
+use clap::ValueEnum;
+
+use crate::emulator::Frame;
+
+pub const DEFAULT_STRENGTH: f32 = 0.6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FilterMode {
+    /// Present the raw buffer.
+    None,
+    /// Let lit pixels fade out over a few frames, like a CRT phosphor.
+    Phosphor,
+    /// Show pixels lit in the current or the previous frame.
+    Or,
+}
+
+// Sprites are erased and redrawn with XOR, so most games flicker when the raw buffer is shown.
+// The filter only works on the copy presented by the window, emulation state is never touched.
+// It goes by which pixels are lit, their colours depend on the palette and the colour board.
+pub struct DisplayFilter {
+    mode: FilterMode,
+    strength: f32,
+    // None before the first frame.
+    previous: Option<Frame>,
+    // the last output and the number of the published frame it was made from. The frontend shows
+    // a frame again when no new one came, which must not fade it any further.
+    shown: Option<(u64, Frame)>,
+}
+
+impl DisplayFilter {
+    pub fn init(mode: FilterMode, strength: f32) -> Self {
+        Self {
+            mode,
+            strength: strength.clamp(0.0, 1.0),
+            previous: None,
+            shown: None,
+        }
+    }
+
+    // filters the published frame with the given number, once per frame.
+    pub fn apply(&mut self, frame: &Frame, number: u64) -> Frame {
+        if let Some((shown, output)) = self.shown {
+            if shown == number {
+                return output;
+            }
+        }
+
+        let mut output = *frame;
+        let previous = self.previous.unwrap_or(*frame);
+
+        match self.mode {
+            FilterMode::None => {}
+            // unlit pixels fade from what was shown there towards the background, lit ones show
+            // their colour right away. Fading pixels do not count as lit.
+            FilterMode::Phosphor => {
+                for (pixel, colour) in output.pixels.iter_mut().enumerate() {
+                    if !frame.lit[pixel] {
+                        *colour = blend(*colour, previous.pixels[pixel], self.strength);
+                    }
+                }
+                self.previous = Some(output);
+            }
+            // pixels lit in the previous frame keep their colour from it.
+            FilterMode::Or => {
+                for pixel in 0..output.pixels.len() {
+                    if !frame.lit[pixel] && previous.lit[pixel] {
+                        output.pixels[pixel] = previous.pixels[pixel];
+                        output.lit[pixel] = true;
+                    }
+                }
+                self.previous = Some(*frame);
+            }
+        }
+
+        self.shown = Some((number, output));
+        output
+    }
+}
+
+// moves every colour channel of a 0RGB pixel from one colour towards another, a factor of 1.0
+// keeping it at the first.
+fn blend(to: u32, from: u32, factor: f32) -> u32 {
+    (0..3).fold(0, |acc, channel| {
+        let shift = channel * 8;
+        let (to, from) = (
+            ((to >> shift) & 0xFF) as f32,
+            ((from >> shift) & 0xFF) as f32,
+        );
+        let value = to + (from - to) * factor;
+        acc | ((value as u32) << shift)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::palette::Palette;
+    use crate::emulator::{HEIGHT, WIDTH};
+
+    // a frame in the palette's background with the given pixels lit.
+    fn frame(palette: Palette, lit: &[usize]) -> Frame {
+        let mut frame = Frame {
+            pixels: [palette.background; WIDTH * HEIGHT],
+            lit: [false; WIDTH * HEIGHT],
+        };
+        for &pixel in lit {
+            frame.pixels[pixel] = palette.foreground;
+            frame.lit[pixel] = true;
+        }
+        frame
+    }
+
+    #[test]
+    fn test_none_passes_frame_through() {
+        let mut filter = DisplayFilter::init(FilterMode::None, DEFAULT_STRENGTH);
+        let lit = frame(Palette::default(), &[10]);
+
+        assert_eq!(filter.apply(&lit, 1), lit);
+        assert_eq!(
+            filter.apply(&frame(Palette::default(), &[]), 2).pixels[10],
+            0x0
+        );
+    }
+
+    #[test]
+    fn test_phosphor_decays_erased_pixels() {
+        let mut filter = DisplayFilter::init(FilterMode::Phosphor, 0.5);
+        let blank = frame(Palette::default(), &[]);
+
+        filter.apply(&frame(Palette::default(), &[10]), 1);
+        let output = filter.apply(&blank, 2);
+        assert_eq!(output.pixels[10], 0x7F7F7F);
+        assert!(!output.lit[10]);
+
+        let output = filter.apply(&blank, 3);
+        assert_eq!(output.pixels[10], 0x3F3F3F);
+    }
+
+    #[test]
+    fn test_phosphor_fades_towards_the_background() {
+        let palette = Palette {
+            background: 0xFFFFFF,
+            foreground: 0x000000,
+        };
+        let mut filter = DisplayFilter::init(FilterMode::Phosphor, 0.5);
+
+        assert_eq!(filter.apply(&frame(palette, &[]), 1).pixels[10], 0xFFFFFF);
+        // newly lit pixels show up at once, erased ones fade out.
+        assert_eq!(filter.apply(&frame(palette, &[10]), 2).pixels[10], 0x000000);
+        assert_eq!(filter.apply(&frame(palette, &[]), 3).pixels[10], 0x7F7F7F);
+    }
+
+    #[test]
+    fn test_phosphor_lit_pixels_stay_at_full_brightness() {
+        let mut filter = DisplayFilter::init(FilterMode::Phosphor, 1.0);
+        let lit = frame(Palette::default(), &[10]);
+
+        filter.apply(&lit, 1);
+
+        assert_eq!(filter.apply(&lit, 2).pixels[10], 0xFFFFFF);
+    }
+
+    #[test]
+    fn test_phosphor_decays_once_per_frame() {
+        let mut filter = DisplayFilter::init(FilterMode::Phosphor, 0.5);
+        let blank = frame(Palette::default(), &[]);
+
+        filter.apply(&frame(Palette::default(), &[10]), 1);
+        // the same frame presented again keeps its brightness.
+        for _ in 0..3 {
+            assert_eq!(filter.apply(&blank, 2).pixels[10], 0x7F7F7F);
+        }
+        assert_eq!(filter.apply(&blank, 3).pixels[10], 0x3F3F3F);
+    }
+
+    #[test]
+    fn test_or_combines_last_two_frames() {
+        let mut filter = DisplayFilter::init(FilterMode::Or, DEFAULT_STRENGTH);
+
+        filter.apply(&frame(Palette::default(), &[10]), 1);
+        let output = filter.apply(&frame(Palette::default(), &[11]), 2);
+        assert_eq!(output.pixels[10], 0xFFFFFF);
+        assert_eq!(output.pixels[11], 0xFFFFFF);
+        assert!(output.lit[10]);
+
+        // only the previous frame is kept.
+        let output = filter.apply(&frame(Palette::default(), &[]), 3);
+        assert_eq!(output.pixels[10], 0x0);
+        assert_eq!(output.pixels[11], 0xFFFFFF);
+    }
+
+    #[test]
+    fn test_or_keeps_the_colours() {
+        let palette = Palette {
+            background: 0x112233,
+            foreground: 0xFFAA00,
+        };
+        let mut filter = DisplayFilter::init(FilterMode::Or, DEFAULT_STRENGTH);
+
+        filter.apply(&frame(palette, &[10]), 1);
+        let output = filter.apply(&frame(palette, &[]), 2);
+        assert_eq!(output.pixels[10], 0xFFAA00);
+        assert_eq!(output.pixels[11], 0x112233);
+
+        // a pixel lit in both frames, as on the colour board, shows its current colour.
+        let mut red = frame(palette, &[10]);
+        red.pixels[10] = 0xFF0000;
+        let mut blue = frame(palette, &[10]);
+        blue.pixels[10] = 0x0000FF;
+        filter.apply(&red, 3);
+        assert_eq!(filter.apply(&blue, 4).pixels[10], 0x0000FF);
+    }
+}
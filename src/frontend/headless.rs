@@ -0,0 +1,21 @@
+use super::Frontend;
+use crate::emulator::Frame;
+
+// runs the emulator without any output or input, for scripts and servers.
+pub struct HeadlessFrontend;
+
+impl Frontend for HeadlessFrontend {
+    fn present(&mut self, _frame: &Frame) {}
+
+    fn poll_input(&mut self) -> u16 {
+        0x00
+    }
+
+    fn play_tone(&mut self) {}
+
+    fn stop_tone(&mut self) {}
+
+    fn should_quit(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::VecDeque;
+
+use super::audio::{AudioBackend, CapturingBackend};
+use super::{Control, Frontend};
+use crate::emulator::Frame;
+
+// A frontend for tests: feeds scripted key states and keeps everything it was given.
+pub struct RecordingFrontend {
+    pub frames: Vec<Frame>,
+    pub audio: CapturingBackend,
+    // held for the whole run.
+    pub controls: Vec<Control>,
+    inputs: VecDeque<u16>,
+    frame_limit: usize,
+}
+
+impl RecordingFrontend {
+    // once the inputs run out the last key state stays held, the frontend quits after frame_limit frames.
+    pub fn init(inputs: impl IntoIterator<Item = u16>, frame_limit: usize) -> Self {
+        Self {
+            frames: Vec::new(),
+            audio: CapturingBackend::default(),
+            controls: Vec::new(),
+            inputs: inputs.into_iter().collect(),
+            frame_limit,
+        }
+    }
+}
+
+impl Frontend for RecordingFrontend {
+    fn present(&mut self, frame: &Frame) {
+        self.frames.push(*frame);
+        self.audio.end_frame();
+    }
+
+    fn poll_input(&mut self) -> u16 {
+        if self.inputs.len() > 1 {
+            self.inputs.pop_front().unwrap()
+        } else {
+            self.inputs.front().copied().unwrap_or(0x00)
+        }
+    }
+
+    fn poll_controls(&mut self) -> Vec<Control> {
+        self.controls.clone()
+    }
+
+    fn play_tone(&mut self) {
+        self.audio.play();
+    }
+
+    fn stop_tone(&mut self) {
+        self.audio.stop();
+    }
+
+    fn should_quit(&self) -> bool {
+        self.frames.len() >= self.frame_limit
+    }
+}
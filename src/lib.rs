@@ -1,2 +1,6 @@
+pub mod capture;
+pub mod console;
 pub mod emulator;
+pub mod frontend;
+pub mod terminal;
 pub mod window;
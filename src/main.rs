@@ -1,52 +1,307 @@
-use anyhow::Result;
-use clap::Parser;
-use rusty_chip_8::{emulator, window};
-use std::path::Path;
-use std::sync::{Arc, Mutex};
+use clap::{Parser, ValueEnum};
+use rusty_chip_8::emulator::audio_recorder::AudioRecorder;
+use rusty_chip_8::emulator::palette::parse_color;
+use rusty_chip_8::emulator::quirks::{MemoryAccess, Platform};
+use rusty_chip_8::emulator::rom_source::{self, RomSource};
+use rusty_chip_8::emulator::video_recorder::VideoRecorder;
+use rusty_chip_8::emulator::vip::VipImages;
+use rusty_chip_8::emulator::{self, Outcome, Overrides, Rom, Shared};
+use rusty_chip_8::frontend::audio::tone::{self, ToneConfig, Waveform};
+use rusty_chip_8::frontend::display_filter::{self, DisplayFilter, FilterMode};
+use rusty_chip_8::frontend::headless::HeadlessFrontend;
+use rusty_chip_8::frontend::{self, Frontend};
+use rusty_chip_8::terminal::{Glyphs, TerminalFrontend};
+use rusty_chip_8::window::WindowFrontend;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc;
 use std::thread;
+use std::time::Duration;
 
 /// A chip-8 emulator
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// The path to the program to be loaded
+    /// The path to the program to be loaded: a '.ch8', '.c8', '.c8x', '.sc8' or '.xo8' file, an Octo cartridge '.gif', a '.zip' archive or '-' for stdin. A menu of the ROM directory is shown without it, except in the headless frontend where it is required.
     #[arg(short, long)]
-    rom: String,
+    rom: Option<PathBuf>,
+
+    /// The ROM to load from a '.zip' archive holding several.
+    #[arg(long, requires = "rom")]
+    entry: Option<String>,
+
+    /// Directory listed by the ROM menu, F1 returns to it.
+    #[arg(long, default_value = "roms")]
+    rom_dir: PathBuf,
 
     /// Run in debug mode where instructions are executed step by step after a N keypress.
     #[arg(short, long, default_value_t = false)]
     debug: bool,
+
+    /// Display filter used to reduce the flicker of XOR-drawn sprites.
+    #[arg(long, value_enum, default_value_t = FilterMode::None)]
+    filter: FilterMode,
+
+    /// Strength of the display filter between 0.0 and 1.0, the phosphor decay factor per frame.
+    #[arg(long, default_value_t = display_filter::DEFAULT_STRENGTH)]
+    filter_strength: f32,
+
+    /// Where the emulator is displayed.
+    #[arg(long, value_enum, default_value_t = FrontendKind::Window)]
+    frontend: FrontendKind,
+
+    /// Characters used to draw the display in the terminal frontend.
+    #[arg(long, value_enum, default_value_t = Glyphs::HalfBlock)]
+    glyphs: Glyphs,
+
+    /// Shape of the buzzer sound wave.
+    #[arg(long, value_enum, default_value_t = Waveform::Sine)]
+    waveform: Waveform,
+
+    /// Pitch of the buzzer in Hz.
+    #[arg(long, default_value_t = 440.0, value_parser = tone::parse_frequency)]
+    pitch: f32,
+
+    /// Volume of the buzzer between 0.0 and 1.0.
+    #[arg(long, default_value_t = 0.2)]
+    volume: f32,
+
+    /// Fade in time of the buzzer in milliseconds.
+    #[arg(long, default_value_t = 5)]
+    attack: u64,
+
+    /// Fade out time of the buzzer in milliseconds.
+    #[arg(long, default_value_t = 5)]
+    release: u64,
+
+    /// Render the buzzer into the given WAV file, in emulated time.
+    #[arg(long)]
+    record_audio: Option<PathBuf>,
+
+    /// Capture every emulated frame into the given '.gif' or '.png' (APNG) file. F9 toggles recording.
+    #[arg(long)]
+    record_video: Option<PathBuf>,
+
+    /// Directory where F12 screenshots and F9 recordings are saved.
+    #[arg(long, default_value = ".")]
+    capture_dir: PathBuf,
+
+    /// Size of a display pixel in screenshots and recorded videos.
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u64).range(1..=64))]
+    capture_scale: u64,
+
+    /// Reload the ROM whenever the file changes.
+    #[arg(short, long, default_value_t = false)]
+    watch: bool,
+
+    /// Machine whose quirks are emulated, by default the one the ROM database recommends.
+    #[arg(long, value_enum)]
+    platform: Option<Platform>,
+
+    /// Instructions executed per second up to 1000000, by default the ROM database's or 700.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..=emulator::MAX_SPEED))]
+    speed: Option<u64>,
+
+    /// Colour of lit pixels as #RRGGBB, by default the ROM database's or white.
+    #[arg(long, value_parser = parse_color)]
+    foreground: Option<u32>,
+
+    /// Colour of unlit pixels as #RRGGBB, by default the ROM database's or black.
+    #[arg(long, value_parser = parse_color)]
+    background: Option<u32>,
+
+    /// Nested subroutine calls before the stack overflows, by default 12 for the VIP, 32 for SUPER-CHIP and 16 otherwise.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..=64))]
+    stack_depth: Option<u64>,
+
+    /// Keep the stack in emulated memory at 0xEA0 like the VIP, for programs that read it.
+    #[arg(long, default_value_t = false)]
+    memory_stack: bool,
+
+    /// What reads and writes past the end of memory do, by default they wrap around.
+    #[arg(long, value_enum)]
+    memory_access: Option<MemoryAccess>,
+
+    /// Give every instruction the time it took on the COSMAC VIP instead of running at a fixed --speed.
+    #[arg(long, default_value_t = false)]
+    vip_timing: bool,
+
+    /// Run programs on an emulated COSMAC VIP with this image of its CHIP-8 interpreter, loaded at 0x000.
+    #[arg(long, conflicts_with_all = ["speed", "stack_depth", "memory_stack", "memory_access", "vip_timing"])]
+    vip_interpreter: Option<PathBuf>,
+
+    /// Image of the VIP's monitor ROM, for interpreters that call into more of it than the display interrupt.
+    #[arg(long, requires = "vip_interpreter")]
+    vip_monitor: Option<PathBuf>,
+
+    /// End the run after this many seconds of emulated time, exiting with code 2.
+    #[arg(long)]
+    timeout: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum FrontendKind {
+    /// A native window.
+    Window,
+    /// ANSI output for SSH sessions and headless servers.
+    Terminal,
+    /// No output or input at all, exits once the program halts.
+    Headless,
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let args = Args::parse();
-    let path = Path::new(&args.rom);
 
-    if !path.exists() {
-        eprintln!(
-            "Error: The specified ROM path '{}' does not exist.",
-            args.rom
-        );
+    match args.rom.as_deref() {
+        Some(path) => validate_rom_path(path, args.entry.as_deref()),
+        // nobody could pick a ROM from the menu.
+        None if args.frontend == FrontendKind::Headless => {
+            eprintln!("Error: The headless frontend needs a ROM, given with --rom.");
+            std::process::exit(1);
+        }
+        None => {}
+    }
+    // steps go on with a key press, which the headless frontend never sends, and the terminal
+    // holds their output back while it draws.
+    if args.debug && args.frontend != FrontendKind::Window {
+        eprintln!("Error: --debug needs the window frontend.");
         std::process::exit(1);
     }
+    let vip = args.vip_interpreter.as_deref().map(|interpreter| {
+        VipImages::load(interpreter, args.vip_monitor.as_deref()).unwrap_or_else(|e| {
+            eprintln!("Error: VIP images could not be loaded. ({})", e);
+            std::process::exit(1);
+        })
+    });
 
-    if path.extension().and_then(|ext| ext.to_str()) != Some("ch8") {
-        eprintln!("Error: ROM must have a '.ch8' extension.");
-        std::process::exit(1);
+    let tone_config = ToneConfig {
+        waveform: args.waveform,
+        frequency: args.pitch,
+        volume: args.volume.clamp(0.0, 1.0),
+        attack: Duration::from_millis(args.attack),
+        release: Duration::from_millis(args.release),
+    };
+
+    let overrides = Overrides {
+        platform: args.platform,
+        speed: args.speed,
+        foreground: args.foreground,
+        background: args.background,
+        stack_depth: args.stack_depth.map(|depth| depth as usize),
+        memory_stack: args.memory_stack,
+        memory_access: args.memory_access,
+        vip_timing: args.vip_timing,
+    };
+    // the ROM is read and checked before any frontend is up, so its problems end the run cleanly.
+    let rom = args
+        .rom
+        .map(|path| Rom::load(RomSource::init(path, args.entry), &overrides, vip.is_some()))
+        .transpose()
+        .unwrap_or_else(|e| {
+            eprintln!("Error: Rom could not be opened. ({})", e);
+            std::process::exit(1);
+        });
+
+    let audio_recorder = args
+        .record_audio
+        .map(|path| AudioRecorder::init(&path, tone_config))
+        .transpose()
+        .unwrap_or_else(|e| {
+            eprintln!("Error: Audio recording could not be started. ({})", e);
+            std::process::exit(1);
+        });
+
+    let video_recorder = args
+        .record_video
+        .as_deref()
+        .map(|path| VideoRecorder::init(path, args.capture_scale as usize))
+        .transpose()
+        .unwrap_or_else(|e| {
+            eprintln!("Error: Video recording could not be started. ({})", e);
+            std::process::exit(1);
+        });
+    if let Some(path) = &args.record_video {
+        eprintln!("Recording video to '{}'.", path.display());
     }
 
-    let key_map = Arc::new(Mutex::new(0u16));
-    let display_buffer = Arc::new(Mutex::new([0u32; window::WIDTH * window::HEIGHT]));
+    let shared = Shared::init();
+    let (commands, command_receiver) = mpsc::channel();
+
+    let options = emulator::Options {
+        debug: args.debug,
+        record_audio: audio_recorder,
+        record_video: video_recorder,
+        capture_dir: args.capture_dir,
+        capture_scale: args.capture_scale as usize,
+        watch: args.watch,
+        rom_dir: args.rom_dir,
+        overrides,
+        exit_on_halt: args.frontend == FrontendKind::Headless,
+        timeout: args.timeout,
+        vip,
+    };
 
     // emulator is ran in separate thread so it can work independently from the window.
-    thread::spawn({ 
-        let display_buffer = Arc::clone(&display_buffer);
-        let key_map = Arc::clone(&key_map);
-        move || emulator::run(args.rom, display_buffer, key_map, args.debug)
+    let emulator_thread = thread::spawn({
+        let shared = shared.clone();
+        move || {
+            // a crashing program should still stop the frontend and fail the run.
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+                emulator::run(rom, shared.clone(), command_receiver, options)
+            }));
+            *shared.quit.lock().unwrap() = true;
+            outcome.unwrap_or(Outcome::Failed)
+        }
     });
 
     // window has to run on main thread.
-    window::run(display_buffer, key_map);
+    let mut frontend: Box<dyn Frontend> = match args.frontend {
+        FrontendKind::Window => Box::new(WindowFrontend::init(tone_config)),
+        FrontendKind::Terminal => Box::new(TerminalFrontend::init(args.glyphs, tone_config)),
+        FrontendKind::Headless => Box::new(HeadlessFrontend),
+    };
+    let filter = DisplayFilter::init(args.filter, args.filter_strength);
+    frontend::run(frontend.as_mut(), shared.clone(), commands, filter);
+
+    // let the emulator finish its recordings before exiting.
+    *shared.quit.lock().unwrap() = true;
+    let outcome = emulator_thread.join().unwrap_or(Outcome::Failed);
+
+    // the frontend restores the terminal and closes its audio when dropped.
+    drop(frontend);
+    ExitCode::from(outcome.exit_code())
+}
+
+fn validate_rom_path(path: &Path, entry: Option<&str>) {
+    let archive = matches!(
+        RomSource::init(path.to_path_buf(), None),
+        RomSource::Archive { .. }
+    );
+    if entry.is_some() && !archive {
+        eprintln!("Error: --entry only picks a ROM out of a '.zip' archive.");
+        std::process::exit(1);
+    }
+
+    if path.as_os_str() == "-" {
+        return;
+    }
 
-    Ok(())
+    if !path.exists() {
+        eprintln!(
+            "Error: The specified ROM path '{}' does not exist.",
+            path.display()
+        );
+        std::process::exit(1);
+    }
+
+    if !rom_source::is_loadable(path) {
+        eprintln!(
+            "Error: ROM must have one of the {} extensions or be a '.zip' archive.",
+            rom_source::ROM_EXTENSIONS
+                .map(|ext| format!("'.{}'", ext))
+                .join(", ")
+        );
+        std::process::exit(1);
+    }
 }
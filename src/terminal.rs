@@ -0,0 +1,335 @@
+use clap::ValueEnum;
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+use key_bindings::create_bindings;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::console;
+use crate::emulator::{Frame, HEIGHT, WIDTH};
+use crate::frontend::audio::tone::ToneConfig;
+use crate::frontend::audio::{self, AudioBackend};
+use crate::frontend::{Command, Control, Frontend};
+
+// most terminals only report presses, so a key counts as held until its auto-repeat stops.
+const KEY_HOLD_FRAMES: u8 = 8;
+const MUTE_KEY: char = 'm';
+const HOTKEYS: [(KeyCode, Command); 8] = [
+    (KeyCode::F(1), Command::Menu),
+    (KeyCode::Up, Command::MenuUp),
+    (KeyCode::Down, Command::MenuDown),
+    (KeyCode::Enter, Command::MenuSelect),
+    (KeyCode::F(5), Command::Reset),
+    (KeyCode::F(6), Command::HardReset),
+    (KeyCode::F(9), Command::ToggleVideoRecording),
+    (KeyCode::F(12), Command::Screenshot),
+];
+const CONTROLS: [(KeyCode, Control); 6] = [
+    (KeyCode::Up, Control::Up),
+    (KeyCode::Down, Control::Down),
+    (KeyCode::Left, Control::Left),
+    (KeyCode::Right, Control::Right),
+    (KeyCode::Char(' '), Control::A),
+    (KeyCode::Tab, Control::B),
+];
+
+mod key_bindings;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Glyphs {
+    /// Two pixels per character cell, keeps the colours of the display.
+    HalfBlock,
+    /// Eight pixels per character cell, monochrome.
+    Braille,
+}
+
+pub struct TerminalFrontend {
+    _terminal: TerminalGuard,
+    glyphs: Glyphs,
+    key_bindings: HashMap<char, u16>,
+    hold_frames: u8,
+    held_keys: [u8; 0x10],
+    // frames left for each of CONTROLS.
+    held_controls: [u8; CONTROLS.len()],
+    commands: Vec<Command>,
+    last_frame: Option<Frame>,
+    audio: Box<dyn AudioBackend>,
+    muted: bool,
+    quit: bool,
+}
+
+impl TerminalFrontend {
+    pub fn init(glyphs: Glyphs, tone: ToneConfig) -> Self {
+        // set up audio first, its warnings would be lost on the alternate screen.
+        let audio = audio::default_backend(tone);
+        let terminal = TerminalGuard::init().expect("Terminal could not be set up.");
+        // terminals with the keyboard enhancement protocol report releases, no need to guess.
+        let hold_frames = if terminal.reports_releases {
+            u8::MAX
+        } else {
+            KEY_HOLD_FRAMES
+        };
+
+        Self {
+            _terminal: terminal,
+            glyphs,
+            key_bindings: create_bindings(),
+            hold_frames,
+            held_keys: [0u8; 0x10],
+            held_controls: [0u8; CONTROLS.len()],
+            commands: Vec::new(),
+            last_frame: None,
+            audio,
+            muted: false,
+            quit: false,
+        }
+    }
+}
+
+impl Frontend for TerminalFrontend {
+    fn present(&mut self, frame: &Frame) {
+        self.audio.end_frame();
+
+        // redrawing the whole screen is expensive over slow connections, skip unchanged frames.
+        if self.last_frame == Some(*frame) {
+            return;
+        }
+
+        let output = draw(frame, self.glyphs).expect("Frame could not be drawn.");
+        let mut stdout = io::stdout();
+        stdout.write_all(&output).unwrap();
+        stdout.flush().unwrap();
+        self.last_frame = Some(*frame);
+    }
+
+    fn poll_input(&mut self) -> u16 {
+        self.held_keys
+            .iter_mut()
+            .chain(self.held_controls.iter_mut())
+            .for_each(|frames| *frames = frames.saturating_sub(1));
+
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            let Ok(Event::Key(key_event)) = event::read() else {
+                continue;
+            };
+
+            if exit(&key_event) {
+                self.quit = true;
+                break;
+            }
+
+            if key_event.kind == KeyEventKind::Press {
+                self.commands.extend(
+                    HOTKEYS
+                        .iter()
+                        .filter(|(key, _)| *key == key_event.code)
+                        .map(|(_, command)| *command),
+                );
+            }
+
+            if let Some(control) = CONTROLS.iter().position(|(key, _)| *key == key_event.code) {
+                self.held_controls[control] = match key_event.kind {
+                    KeyEventKind::Release => 0,
+                    _ => self.hold_frames,
+                };
+            }
+
+            let KeyCode::Char(key) = key_event.code else {
+                continue;
+            };
+            if key.to_ascii_lowercase() == MUTE_KEY && key_event.kind == KeyEventKind::Press {
+                self.muted = !self.muted;
+                self.audio.set_muted(self.muted);
+                continue;
+            }
+            if let Some(flag) = self.key_bindings.get(&key.to_ascii_lowercase()) {
+                let key = flag.trailing_zeros() as usize;
+                self.held_keys[key] = match key_event.kind {
+                    KeyEventKind::Release => 0,
+                    _ => self.hold_frames,
+                };
+            }
+        }
+
+        self.held_keys
+            .iter()
+            .enumerate()
+            .filter(|(_, frames)| **frames > 0)
+            .fold(0x00, |flags, (key, _)| flags | (0b1 << key))
+    }
+
+    fn poll_controls(&mut self) -> Vec<Control> {
+        CONTROLS
+            .iter()
+            .zip(self.held_controls)
+            .filter(|(_, frames)| *frames > 0)
+            .map(|((_, control), _)| *control)
+            .collect()
+    }
+
+    fn poll_commands(&mut self) -> Vec<Command> {
+        std::mem::take(&mut self.commands)
+    }
+
+    fn play_tone(&mut self) {
+        self.audio.play();
+    }
+
+    fn stop_tone(&mut self) {
+        self.audio.stop();
+    }
+
+    fn should_quit(&self) -> bool {
+        self.quit
+    }
+}
+
+fn exit(key_event: &KeyEvent) -> bool {
+    // raw mode swallows ctrl+c, so it has to be handled by hand.
+    key_event.code == KeyCode::Esc
+        || (key_event.code == KeyCode::Char('c')
+            && key_event.modifiers.contains(KeyModifiers::CONTROL))
+}
+
+fn draw(frame: &Frame, glyphs: Glyphs) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+
+    match glyphs {
+        Glyphs::HalfBlock => {
+            let mut colors = None;
+            for row in 0..HEIGHT / 2 {
+                queue!(output, cursor::MoveTo(0, row as u16))?;
+                for x in 0..WIDTH {
+                    let top = frame.pixels[row * 2 * WIDTH + x];
+                    let bottom = frame.pixels[(row * 2 + 1) * WIDTH + x];
+
+                    // only emit colour changes, most neighbouring cells share them.
+                    if colors != Some((top, bottom)) {
+                        queue!(
+                            output,
+                            SetForegroundColor(to_color(top)),
+                            SetBackgroundColor(to_color(bottom))
+                        )?;
+                        colors = Some((top, bottom));
+                    }
+                    queue!(output, Print('▀'))?;
+                }
+            }
+            queue!(output, ResetColor)?;
+        }
+        Glyphs::Braille => {
+            for row in 0..HEIGHT / 4 {
+                queue!(output, cursor::MoveTo(0, row as u16))?;
+                let line: String = (0..WIDTH / 2)
+                    .map(|column| braille_cell(frame, column, row))
+                    .collect();
+                queue!(output, Print(line))?;
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+// a braille character covers a 2x4 block of pixels, dots are numbered column first. Only lit
+// pixels get a dot, whatever the colours, pixels fading out in the filter are not lit.
+fn braille_cell(frame: &Frame, column: usize, row: usize) -> char {
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+    let mut pattern = 0;
+    for (y_offset, dots) in DOTS.iter().enumerate() {
+        for (x_offset, dot) in dots.iter().enumerate() {
+            if frame.lit[(row * 4 + y_offset) * WIDTH + column * 2 + x_offset] {
+                pattern |= dot;
+            }
+        }
+    }
+
+    char::from_u32(0x2800 + pattern).unwrap()
+}
+
+fn to_color(pixel: u32) -> Color {
+    let [_, r, g, b] = pixel.to_be_bytes();
+    Color::Rgb { r, g, b }
+}
+
+// puts the terminal into raw mode on an alternate screen and restores it when dropped. Messages
+// are held back meanwhile, they would land in the middle of the display.
+struct TerminalGuard {
+    reports_releases: bool,
+}
+
+impl TerminalGuard {
+    fn init() -> io::Result<Self> {
+        // the emulator is already running, hold its messages before the screen is taken. Should
+        // the set up fail, dropping the guard restores everything again.
+        console::hold();
+        let mut guard = Self {
+            reports_releases: false,
+        };
+
+        terminal::enable_raw_mode()?;
+        execute!(
+            io::stdout(),
+            terminal::EnterAlternateScreen,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::Hide
+        )?;
+
+        if terminal::supports_keyboard_enhancement().unwrap_or(false) {
+            execute!(
+                io::stdout(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+            guard.reports_releases = true;
+        }
+
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if self.reports_releases {
+            let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(
+            io::stdout(),
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+        console::release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_braille_cell() {
+        let mut frame = Frame::blank();
+        frame.lit[0] = true; // top left dot
+        frame.lit[3 * WIDTH + 1] = true; // bottom right dot
+
+        assert_eq!(braille_cell(&frame, 0, 0), '⢁');
+        assert_eq!(braille_cell(&frame, 1, 0), '⠀');
+    }
+
+    #[test]
+    fn test_braille_cell_ignores_unlit_colours() {
+        // the blue CHIP-8X background, with a pixel fading out.
+        let mut frame = Frame::blank();
+        frame.pixels = [0x000080; WIDTH * HEIGHT];
+        frame.pixels[0] = 0xC0C0C0;
+
+        assert_eq!(braille_cell(&frame, 0, 0), '⠀');
+    }
+}
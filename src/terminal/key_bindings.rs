@@ -0,0 +1,23 @@
+use std::collections::HashMap;
+
+// same layout as the window bindings, keyed by the character the terminal reports.
+pub fn create_bindings() -> HashMap<char, u16> {
+    HashMap::from([
+        ('1', 0b1 << 1),
+        ('2', 0b1 << 2),
+        ('3', 0b1 << 3),
+        ('4', 0b1 << 12),
+        ('q', 0b1 << 4),
+        ('w', 0b1 << 5),
+        ('e', 0b1 << 6),
+        ('r', 0b1 << 13),
+        ('a', 0b1 << 7),
+        ('s', 0b1 << 8),
+        ('d', 0b1 << 9),
+        ('f', 0b1 << 14),
+        ('z', 0b1 << 10),
+        ('x', 0b1),
+        ('c', 0b1 << 11),
+        ('v', 0b1 << 15),
+    ])
+}
@@ -1,61 +1,126 @@
-use key_bindings::create_bindings;
-use minifb::{Key, Window, WindowOptions};
-use std::sync::{Arc, Mutex};
+use key_bindings::{create_bindings, create_second_bindings};
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+use std::collections::HashMap;
 
-pub const WIDTH: usize = 64;
-pub const HEIGHT: usize = 32;
-pub type DisplayBuffer = [u32; WIDTH * HEIGHT];
-
-// TODO: move it to a config file
-const REFRESH_RATE: usize = 60;
+use crate::emulator::{Frame, HEIGHT, WIDTH};
+use crate::frontend::audio::tone::ToneConfig;
+use crate::frontend::audio::{self, AudioBackend};
+use crate::frontend::{Command, Control, Frontend};
 
 mod key_bindings;
 
-pub fn run(display_buffer: Arc<Mutex<DisplayBuffer>>, key_map: Arc<Mutex<u16>>) {
-    let mut window = init();
-    let mut buffer: DisplayBuffer; // 64x32 framebuffer
-    let key_bindings = create_bindings();
+const MUTE_KEY: Key = Key::M;
+const HOTKEYS: [(Key, Command); 8] = [
+    (Key::F1, Command::Menu),
+    (Key::Up, Command::MenuUp),
+    (Key::Down, Command::MenuDown),
+    (Key::Enter, Command::MenuSelect),
+    (Key::F5, Command::Reset),
+    (Key::F6, Command::HardReset),
+    (Key::F9, Command::ToggleVideoRecording),
+    (Key::F12, Command::Screenshot),
+];
+const CONTROLS: [(Key, Control); 6] = [
+    (Key::Up, Control::Up),
+    (Key::Down, Control::Down),
+    (Key::Left, Control::Left),
+    (Key::Right, Control::Right),
+    (Key::Space, Control::A),
+    (Key::Tab, Control::B),
+];
+
+pub struct WindowFrontend {
+    window: Window,
+    key_bindings: HashMap<Key, u16>,
+    second_key_bindings: HashMap<Key, u16>,
+    audio: Box<dyn AudioBackend>,
+    muted: bool,
+}
 
-    loop {
-        if exit(&window) {
-            break;
+impl WindowFrontend {
+    pub fn init(tone: ToneConfig) -> Self {
+        let audio = audio::default_backend(tone);
+        let window = Window::new(
+            "Rusty Chip-8",
+            WIDTH,
+            HEIGHT,
+            WindowOptions {
+                resize: false,
+                scale: minifb::Scale::X16, // Scale up for visibility
+                ..WindowOptions::default()
+            },
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+
+        Self {
+            window,
+            key_bindings: create_bindings(),
+            second_key_bindings: create_second_bindings(),
+            audio,
+            muted: false,
         }
+    }
+}
 
-        let mut key_map = key_map.lock().unwrap();
-        *key_map = 0x00;
+impl Frontend for WindowFrontend {
+    fn present(&mut self, frame: &Frame) {
+        self.window
+            .update_with_buffer(&frame.pixels, WIDTH, HEIGHT)
+            .unwrap();
+        self.audio.end_frame();
+    }
 
-        window.get_keys().iter().for_each(|key| {
-            if key_bindings.contains_key(key) {
-                *key_map ^= key_bindings[key];
-            }
-        });
-        drop(key_map);
+    fn poll_input(&mut self) -> u16 {
+        if self.window.is_key_pressed(MUTE_KEY, KeyRepeat::No) {
+            self.muted = !self.muted;
+            self.audio.set_muted(self.muted);
+        }
 
-        let display_buffer = display_buffer.lock().unwrap();
-        buffer = *display_buffer;
-        drop(display_buffer);
+        // keys pressed since the last update count as held too, so taps between frames are kept.
+        let pressed = self.window.get_keys_pressed(KeyRepeat::No);
+        self.window
+            .get_keys()
+            .iter()
+            .chain(&pressed)
+            .filter_map(|key| self.key_bindings.get(key))
+            .fold(0x00, |key_map, flag| key_map | flag)
+    }
 
-        window.update_with_buffer(&buffer, WIDTH, HEIGHT).unwrap();
+    fn poll_second_keypad(&mut self) -> u16 {
+        let pressed = self.window.get_keys_pressed(KeyRepeat::No);
+        self.window
+            .get_keys()
+            .iter()
+            .chain(&pressed)
+            .filter_map(|key| self.second_key_bindings.get(key))
+            .fold(0x00, |key_map, flag| key_map | flag)
     }
-}
 
-fn exit(window: &Window) -> bool {
-    !window.is_open() || window.is_key_down(Key::Escape)
-}
+    fn poll_controls(&mut self) -> Vec<Control> {
+        CONTROLS
+            .iter()
+            .filter(|(key, _)| self.window.is_key_down(*key))
+            .map(|(_, control)| *control)
+            .collect()
+    }
 
-fn init() -> Window {
-    let mut window = Window::new(
-        "Rusty Chip-8",
-        WIDTH,
-        HEIGHT,
-        WindowOptions {
-            resize: false,
-            scale: minifb::Scale::X16, // Scale up for visibility
-            ..WindowOptions::default()
-        },
-    )
-    .unwrap_or_else(|e| panic!("{}", e));
-    window.set_target_fps(REFRESH_RATE);
-
-    window
+    fn poll_commands(&mut self) -> Vec<Command> {
+        HOTKEYS
+            .iter()
+            .filter(|(key, _)| self.window.is_key_pressed(*key, KeyRepeat::No))
+            .map(|(_, command)| *command)
+            .collect()
+    }
+
+    fn play_tone(&mut self) {
+        self.audio.play();
+    }
+
+    fn stop_tone(&mut self) {
+        self.audio.stop();
+    }
+
+    fn should_quit(&self) -> bool {
+        !self.window.is_open() || self.window.is_key_down(Key::Escape)
+    }
 }
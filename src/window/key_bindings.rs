@@ -22,3 +22,25 @@ pub fn create_bindings() -> HashMap<Key, u16> {
         (Key::V, 0b1 << 15),
     ])
 }
+
+// the second keypad of CHIP-8X sits on the numeric keypad, digits on their own keys.
+pub fn create_second_bindings() -> HashMap<Key, u16> {
+    HashMap::from([
+        (Key::NumPad0, 0b1),
+        (Key::NumPad1, 0b1 << 1),
+        (Key::NumPad2, 0b1 << 2),
+        (Key::NumPad3, 0b1 << 3),
+        (Key::NumPad4, 0b1 << 4),
+        (Key::NumPad5, 0b1 << 5),
+        (Key::NumPad6, 0b1 << 6),
+        (Key::NumPad7, 0b1 << 7),
+        (Key::NumPad8, 0b1 << 8),
+        (Key::NumPad9, 0b1 << 9),
+        (Key::NumPadSlash, 0b1 << 10),
+        (Key::NumPadAsterisk, 0b1 << 11),
+        (Key::NumPadMinus, 0b1 << 12),
+        (Key::NumPadPlus, 0b1 << 13),
+        (Key::NumPadEnter, 0b1 << 14),
+        (Key::NumPadDot, 0b1 << 15),
+    ])
+}